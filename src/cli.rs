@@ -11,11 +11,14 @@ pub struct Cli {
 #[derive(Debug, Subcommand)]
 pub enum Action {
     /// Start the HTTP server.
-    Serve(crate::serve::Serve),
+    Serve(Box<crate::serve::Serve>),
     /// Initialize a new blog in the current working directory.
     Init(crate::init::Init),
     /// Generate new things from a template.
-    Generate(crate::generate::Generate)
+    Generate(crate::generate::Generate),
+    /// Import Jekyll or Hugo posts into the posts directory.
+    #[command(alias = "migrate")]
+    Import(crate::import::Import),
 }
 
 impl Action {
@@ -24,6 +27,7 @@ impl Action {
             Action::Serve(serve) => serve.log_level,
             Action::Init(init) => init.log_level,
             Action::Generate(generate) => generate.log_level,
+            Action::Import(import) => import.log_level,
         }
     }
 }
@@ -0,0 +1,254 @@
+use std::{error::Error, fs, path::{Path, PathBuf}};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use clap::Parser;
+use log::{debug, info, warn, LevelFilter};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::util::mydatetime::MyDateTime;
+
+#[derive(Debug, Parser)]
+pub struct Import {
+    /// Adjusts the verbosity of the logger.
+    #[arg(long, default_value = "warn")]
+    pub log_level: LevelFilter,
+
+    /// Directory containing the Jekyll or Hugo markdown posts to import.
+    source: PathBuf,
+
+    /// Overwrite posts that already exist in the posts directory.
+    #[arg(long)]
+    force: bool,
+}
+
+/// Front matter in the form µstack expects it.
+#[derive(Debug, Default, Serialize)]
+struct ImportedMetadata {
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    highlight: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    comments: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    draft: bool,
+}
+
+/// Front matter keys that are understood by Jekyll or Hugo, but have no
+/// meaning to µstack. These are dropped silently.
+const IGNORED_KEYS: [&str; 3] = ["layout", "permalink", "slug"];
+
+impl Import {
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all("posts")?;
+
+        let mut imported = 0;
+        for ent in fs::read_dir(&self.source)? {
+            let path = ent?.path();
+
+            let is_markdown = path
+                .extension()
+                .is_some_and(|ext| ext == "md" || ext == "markdown");
+            if !is_markdown || !path.is_file() {
+                debug!("Skipping {path:?}");
+                continue;
+            }
+
+            match self.import_post(&path) {
+                Ok(true) => imported += 1,
+                Ok(false) => {}
+                Err(err) => warn!("Failed to import {path:?}: {err}"),
+            }
+        }
+
+        info!("Imported {imported} posts");
+
+        Ok(())
+    }
+
+    fn import_post(&self, path: &Path) -> Result<bool, Box<dyn Error>> {
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| format!("Invalid file name {path:?}"))?;
+
+        let (filename_date, id) = split_jekyll_filename(stem);
+        let id = sanitize_id(id);
+        if id.is_empty() {
+            return Err(format!("Could not derive a post id from {path:?}").into());
+        }
+
+        let mut out_path = Path::new("posts").join(&id);
+        out_path.set_extension("md");
+
+        if out_path.exists() && !self.force {
+            warn!("Not importing {path:?} because {out_path:?} already exists");
+            return Ok(false);
+        }
+
+        let text = fs::read_to_string(path)?;
+        let (front_matter, body) = split_front_matter(&text)
+            .ok_or_else(|| "Missing a YAML preamble".to_string())?;
+
+        let front_matter: Mapping = serde_yaml::from_str(front_matter)?;
+        let mut metadata = map_front_matter(&id, front_matter);
+
+        if metadata.created.is_none() {
+            metadata.created = filename_date
+                .and_then(|date| Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).single())
+                .map(|date| MyDateTime::from(date).to_string_no_seconds().to_string());
+        }
+
+        if metadata.title.is_empty() {
+            metadata.title = id.replace('-', " ");
+        }
+
+        let output = format!("---\n{}---\n{}", serde_yaml::to_string(&metadata)?, body);
+        fs::write(&out_path, output)?;
+
+        info!("Imported {path:?} as {out_path:?}");
+
+        Ok(true)
+    }
+}
+
+fn map_front_matter(id: &str, front_matter: Mapping) -> ImportedMetadata {
+    let mut metadata = ImportedMetadata::default();
+
+    for (key, value) in front_matter {
+        let Some(key) = key.as_str() else {
+            warn!("{id}: ignoring non-string front matter key {key:?}");
+            continue;
+        };
+
+        match key {
+            "title" => metadata.title = value_to_string(&value).unwrap_or_default(),
+            "author" => metadata.author = value_to_string(&value),
+            "summary" | "description" | "excerpt" => {
+                if metadata.summary.is_none() {
+                    metadata.summary = value_to_string(&value);
+                }
+            }
+            "date" | "created" => {
+                let date = value_to_string(&value);
+                metadata.created = date.as_deref().and_then(parse_date);
+                if metadata.created.is_none() {
+                    warn!("{id}: could not parse date {date:?}");
+                }
+            }
+            "tags" | "categories" | "category" => {
+                for tag in value_to_list(&value) {
+                    let tag = tag.trim().to_string();
+                    if !tag.is_empty() && !metadata.tags.contains(&tag) {
+                        metadata.tags.push(tag);
+                    }
+                }
+            }
+            "highlight" => metadata.highlight = value.as_bool().unwrap_or(false),
+            "comments" => metadata.comments = value.as_bool().unwrap_or(false),
+            "draft" => metadata.draft = value.as_bool().unwrap_or(false),
+            key if IGNORED_KEYS.contains(&key) => {}
+            key => warn!("{id}: unmapped front matter key '{key}'"),
+        }
+    }
+
+    metadata
+}
+
+/// Splits the YAML front matter from the rest of a markdown document.
+fn split_front_matter(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("---")?;
+    let rest = rest.trim_start_matches([' ', '\t']);
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Jekyll posts are named `YYYY-MM-DD-id.md`. Splits the date from the id,
+/// if there is one.
+fn split_jekyll_filename(stem: &str) -> (Option<NaiveDate>, &str) {
+    if stem.len() > 11 && stem.is_char_boundary(11) {
+        let (date, id) = stem.split_at(11);
+        if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d-") {
+            return (Some(date), id);
+        }
+    }
+    (None, stem)
+}
+
+fn sanitize_id(id: &str) -> String {
+    id.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => c,
+            _ => '-',
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Parses the date formats used by Jekyll and Hugo, and converts them to one
+/// µstack understands.
+fn parse_date(date: &str) -> Option<String> {
+    const WITH_OFFSET: [&str; 2] = ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M %z"];
+    const WITHOUT_OFFSET: [&str; 2] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+
+    let date = date.trim();
+
+    let parsed = DateTime::parse_from_rfc3339(date)
+        .ok()
+        .or_else(|| {
+            WITH_OFFSET
+                .iter()
+                .find_map(|fmt| DateTime::<FixedOffset>::parse_from_str(date, fmt).ok())
+        })
+        .or_else(|| {
+            WITHOUT_OFFSET
+                .iter()
+                .find_map(|fmt| NaiveDateTime::parse_from_str(date, fmt).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|d| d.and_hms_opt(0, 0, 0))
+                })
+                .and_then(|naive| Local.from_local_datetime(&naive).single())
+                .map(|local| local.fixed_offset())
+        })?;
+
+    Some(MyDateTime::from(parsed).to_string_no_seconds().to_string())
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Jekyll allows categories and tags to be either a YAML list or a single
+/// space-separated string.
+fn value_to_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::Sequence(seq) => seq.iter().filter_map(value_to_string).collect(),
+        Value::String(s) => s.split_whitespace().map(|s| s.to_string()).collect(),
+        other => value_to_string(other).into_iter().collect(),
+    }
+}
@@ -13,6 +13,7 @@ pub struct Init {
 const DEFAULT_STYLES: &str = include_str!("res/default_styles.css");
 const DEFAULT_CONFIG: &str = include_str!("res/default_index.md");
 const SAMPLE_POST: &str = include_str!("res/sample_post.md");
+const DEFAULT_FAVICON: &[u8] = include_bytes!("res/favicon.png");
 
 impl Init {
     pub fn run(self) -> Result<(), Box<dyn Error>> {
@@ -28,6 +29,14 @@ impl Init {
             file.write_all(DEFAULT_STYLES.as_bytes())?;
         }
 
+        if Path::new("public/favicon.png").exists() {
+            warn!("Not creating favicon.png because it already exists");
+        } else {
+            debug!("Creating default favicon.png");
+            let mut file = fs::File::create("public/favicon.png")?;
+            file.write_all(DEFAULT_FAVICON)?;
+        }
+
         if Path::new("index.md").exists() {
             warn!("Not creating index.md because it already exists");
         } else {
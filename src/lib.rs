@@ -0,0 +1,17 @@
+//! ustack renders and serves blogs written as markdown files. The `ustack`
+//! binary is a thin CLI on top of this library, which can also be embedded
+//! in a larger program.
+
+pub mod cli;
+pub mod generate;
+pub mod import;
+pub mod init;
+pub mod serve;
+pub mod view;
+pub mod util;
+pub mod model;
+
+pub use model::{IndexMetadata, Metadata};
+pub use serve::{Handler, Serve};
+pub use util::{db::PostDb, render_html};
+pub use view::render_post_html;
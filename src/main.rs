@@ -1,17 +1,7 @@
-mod cli;
-mod generate;
-mod init;
-mod serve;
-mod view;
-mod util;
-mod model;
-
 use std::error::Error;
 
 use clap::Parser;
-use cli::Action;
-
-use crate::cli::Cli;
+use ustack::cli::{Action, Cli};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -26,6 +16,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Action::Serve(serve) => serve.run().await?,
         Action::Init(init) => init.run()?,
         Action::Generate(generate) => generate.run()?,
+        Action::Import(import) => import.run()?,
     }
 
     Ok(())
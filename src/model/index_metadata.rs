@@ -8,6 +8,8 @@ pub struct IndexMetadata {
     pub short_title: Option<String>,
     pub author: Option<String>,
     pub summary: Option<String>,
+    /// Description of the RSS feed, when it should differ from the summary.
+    pub rss_description: Option<String>,
     #[serde(default)]
     pub highlight: bool,
     #[serde(default)]
@@ -20,23 +22,301 @@ pub struct IndexMetadata {
     pub lang: String,
     #[serde(default, deserialize_with = "deserialize_opt_url")]
     pub coffee: Option<Url>,
+    /// URL where a post can be edited, with `{id}` in place of its id, e.g.
+    /// https://github.com/me/blog/edit/main/posts/{id}.md
+    #[serde(default, deserialize_with = "deserialize_opt_url_template")]
+    pub edit_url_template: Option<String>,
+    /// Script in the `public` directory that is included on posts with
+    /// comments enabled.
+    #[serde(default, deserialize_with = "deserialize_opt_public_path")]
+    pub comments: Option<String>,
+    #[serde(default)]
+    pub index_layout: IndexLayout,
+    #[serde(default)]
+    pub archive_group_by: ArchiveGroupBy,
+    #[serde(default)]
+    pub markdown: MarkdownOptions,
+    /// Color of the browser UI on mobile, and when the blog is installed as an app.
+    pub theme_color: Option<String>,
+    /// Advertise a search engine at /search?q={searchTerms} to browsers,
+    /// for sites that serve one there, e.g. behind a reverse proxy.
+    #[serde(default)]
+    pub opensearch: bool,
+    /// Icons for installing the blog as an app. Defaults to the 32x32
+    /// `favicon.png` that `ustack init` creates.
+    #[serde(default)]
+    pub app_icons: Vec<AppIcon>,
+    /// Color schemes the styles support, e.g. "light dark".
+    pub color_scheme: Option<String>,
+    /// iTunes tags for the RSS feed, for blogs that are podcasts.
+    pub podcast: Option<PodcastMetadata>,
+    /// Links below the index's header.
+    #[serde(default)]
+    pub nav: NavOptions,
+    /// Names of the icon links in the header, for screen readers.
+    #[serde(default)]
+    pub header_labels: HeaderLabels,
+    /// Posts with more tags than this are warned about, or rejected with
+    /// --strict-frontmatter. Defaults to 20.
+    pub max_tags: Option<usize>,
+    /// Posts with an `updated` date within this many days are marked as
+    /// recently updated.
+    pub recently_updated_days: Option<u32>,
+    /// Posts without an `updated` date are marked by when their file was
+    /// last modified, for sites whose deploys keep file times.
+    #[serde(default)]
+    pub recently_updated_from_mtime: bool,
+    /// Show the number of posts and the date of the newest in posts' footers.
+    #[serde(default)]
+    pub footer_stats: bool,
+}
+
+/// The index's links to the archive and to a random post.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NavOptions {
+    pub archive: NavLinkOptions,
+    pub random: NavLinkOptions,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct NavLinkOptions {
+    pub label: Option<String>,
+    /// Target of the link, relative to the site's URL.
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HeaderLabels {
+    pub rss: String,
+    pub coffee: String,
+}
+
+impl Default for HeaderLabels {
+    fn default() -> Self {
+        Self {
+            rss: "RSS feed".to_string(),
+            coffee: "Support me".to_string(),
+        }
+    }
+}
+
+/// A link in the index's navigation
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavLink {
+    pub label: String,
+    pub href: String,
+}
+
+/// An icon in the `public` directory, for the web app manifest.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AppIcon {
+    #[serde(deserialize_with = "deserialize_public_path")]
+    pub path: String,
+    /// Dimensions of the image, like "192x192", or "any" for SVG.
+    pub sizes: String,
+}
+
+/// The channel-level iTunes tags that Apple Podcasts requires of a feed.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PodcastMetadata {
+    /// Defaults to the site's author.
+    pub author: Option<String>,
+    /// Apple Podcasts categories, with an optional subcategory after a
+    /// slash, e.g. "Arts/Books".
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Cover art in the `public` directory.
+    #[serde(default, deserialize_with = "deserialize_opt_public_path")]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    pub owner: Option<PodcastOwner>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PodcastOwner {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// The markdown rendering options that can be changed per site. Options that
+/// ustack depends on, like front matter and header ids, are not exposed.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct MarkdownOptions {
+    /// Convert straight quotes, dashes and ellipses to their typographic forms.
+    pub smart: bool,
+    /// Treat every newline inside a paragraph as a line break.
+    pub hardbreaks: bool,
+    /// Pass raw HTML in posts through to the page. Only safe when every
+    /// author is trusted.
+    pub unsafe_html: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            smart: true,
+            hardbreaks: false,
+            unsafe_html: true,
+        }
+    }
+}
+
+/// Order of the index page's content and its list of recent posts.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum IndexLayout {
+    /// The contents of index.md, followed by recent posts.
+    #[default]
+    IntroFirst,
+    /// Recent posts, followed by the contents of index.md.
+    ListFirst,
+    /// Only the list of recent posts.
+    ListOnly,
+    /// Only the contents of index.md.
+    IntroOnly,
+}
+
+/// How the archive's posts are grouped under headings.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchiveGroupBy {
+    /// A single list of posts.
+    #[default]
+    None,
+    /// A list for each year that posts were published in.
+    Year,
 }
 
 impl IndexMetadata {
-    pub fn from_yaml<S: AsRef<str>>(yaml: S) -> Result<Self, super::Error> {
-        let deserializer = serde_yaml::Deserializer::from_str(yaml.as_ref());
-        Ok(Self::deserialize(deserializer)?)
+    pub fn from_yaml<S: AsRef<str>>(yaml: S, strict: bool) -> Result<Self, super::Error> {
+        super::from_yaml(yaml.as_ref(), strict)
     }
 
     pub fn twitter_link(&self, post_id: &str) -> Result<Option<Url>, Box<dyn std::error::Error>> {
         let mut url = self.url.clone();
         if self.twitter {
-            url.set_path(&format!("p/{post_id}"));
+            url.path_segments_mut()
+                .expect("url shall be a base")
+                .pop_if_empty()
+                .extend(&["p", post_id]);
             Ok(Some(url))
         } else {
             Ok(None)
         }
     }
+
+    /// The index's navigation links. Their targets are under the path of
+    /// the site's URL, so they still work when the blog isn't at the root.
+    pub fn nav_links(&self) -> Vec<NavLink> {
+        let base = self.url.path().trim_end_matches('/');
+        let link = |options: &NavLinkOptions, label: &str, path: &str| {
+            let path = options.path.as_deref().unwrap_or(path).trim_start_matches('/');
+            NavLink {
+                label: options.label.as_deref().unwrap_or(label).to_string(),
+                href: format!("{base}/{path}"),
+            }
+        };
+
+        vec![
+            link(&self.nav.archive, "Archive", "archive"),
+            link(&self.nav.random, "Random Post", "random"),
+        ]
+    }
+
+    /// Where the post with `post_id` can be edited, if the site says
+    pub fn edit_link(&self, post_id: &str) -> Option<Url> {
+        let template = self.edit_url_template.as_ref()?;
+        Url::parse(&template.replace("{id}", post_id)).ok()
+    }
+
+    /// Generates an OpenSearch description document for the blog.
+    pub fn opensearch_description(&self) -> String {
+        use quick_xml::escape::escape;
+
+        // OpenSearch limits the short name to 16 characters
+        let short_name: String = self.short_title
+            .as_deref()
+            .unwrap_or(&self.title)
+            .chars()
+            .take(16)
+            .collect();
+
+        let mut favicon = self.url.clone();
+        favicon.path_segments_mut()
+            .expect("url shall be a base")
+            .pop_if_empty()
+            .extend(&["public", "favicon.png"]);
+
+        let mut search = self.url.clone();
+        search.path_segments_mut()
+            .expect("url shall be a base")
+            .pop_if_empty()
+            .push("search");
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">"#,
+                "<ShortName>{short_name}</ShortName>",
+                "<Description>Search {title}</Description>",
+                "<InputEncoding>UTF-8</InputEncoding>",
+                r#"<Image width="16" height="16" type="image/png">{favicon}</Image>"#,
+                r#"<Url type="text/html" method="get" template="{search}?q={{searchTerms}}"/>"#,
+                "</OpenSearchDescription>",
+            ),
+            short_name = escape(&short_name),
+            title = escape(&self.title),
+            favicon = escape(favicon.as_str()),
+            search = escape(search.as_str()),
+        )
+    }
+
+    /// Generates a web app manifest, which lets browsers install the blog.
+    pub fn web_manifest(&self) -> String {
+        let start_url = self.url.path();
+        let theme_color = self.theme_color.as_deref().unwrap_or("#ffffff");
+
+        let mut manifest = serde_json::json!({
+            "name": self.title,
+            "short_name": self.short_title.as_deref().unwrap_or(&self.title),
+            "lang": self.lang.replace('_', "-"),
+            "start_url": start_url,
+            "scope": start_url,
+            "display": "minimal-ui",
+            "theme_color": theme_color,
+            "background_color": theme_color,
+        });
+
+        let default_icon = AppIcon {
+            path: "favicon.png".to_string(),
+            sizes: "32x32".to_string(),
+        };
+        let icons = match self.app_icons.as_slice() {
+            [] => std::slice::from_ref(&default_icon),
+            icons => icons,
+        };
+        manifest["icons"] = icons.iter()
+            .map(|icon| {
+                let mut src = self.url.clone();
+                src.path_segments_mut()
+                    .expect("url shall be a base")
+                    .pop_if_empty()
+                    .push("public")
+                    .extend(icon.path.split('/'));
+                serde_json::json!({ "src": src.path(), "sizes": icon.sizes })
+            })
+            .collect();
+
+        if let Some(summary) = &self.summary {
+            manifest["description"] = summary.as_str().into();
+        }
+
+        manifest.to_string()
+    }
 }
 
 fn default_lang() -> String {
@@ -53,10 +333,22 @@ where
     if url.cannot_be_a_base() {
         Err(D::Error::custom("Index URL must be a base URL"))
     } else {
-        Ok(url)
+        Ok(normalize_site_url(url))
     }
 }
 
+/// Ensures the site URL's path ends with a slash, so URLs derived from it
+/// always land beneath it.
+fn normalize_site_url(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    url
+}
+
 fn deserialize_opt_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
 where
     D: Deserializer<'de>,
@@ -71,20 +363,126 @@ where
     }
 }
 
-impl Default for IndexMetadata {
-    fn default() -> Self {
-        Self {
-            // TODO: replace impl of Default with a non-hacky initializer for IndexMetadata
-            url: Url::parse("https://unspecified.com").unwrap(),
-            title: Default::default(),
-            author: Default::default(),
-            summary: Default::default(),
-            highlight: Default::default(),
-            tags: Default::default(),
-            twitter: Default::default(),
-            lang: Default::default(),
-            coffee: Default::default(),
-            short_title: Default::default(),
-        }
+/// A URL with `{id}` somewhere in it
+fn deserialize_opt_url_template<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let template = String::deserialize(deserializer)?;
+    if !template.contains("{id}") {
+        return Err(D::Error::custom("URL template must contain {id}"));
+    }
+    Url::parse(&template.replace("{id}", "id"))
+        .map_err(|err| D::Error::custom(format!("{err}")))?;
+    Ok(Some(template))
+}
+
+pub(super) fn deserialize_opt_public_path<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_public_path(deserializer).map(Some)
+}
+
+fn deserialize_public_path<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let path_str = String::deserialize(deserializer)?;
+    let path = path_str
+        .trim_start_matches('/')
+        .trim_start_matches("public/");
+
+    let is_valid = !path.is_empty() && path.split('/').all(|segment| {
+        !segment.is_empty()
+            && !segment.starts_with('.')
+            && segment.chars().all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.'))
+    });
+
+    if is_valid {
+        Ok(path.to_string())
+    } else {
+        Err(D::Error::custom(format!("{path_str:?} is not a valid path in the public directory")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use url::Url;
+
+    use super::{deserialize_url, IndexMetadata};
+
+    fn site_url(url: &str) -> Url {
+        let deserializer = serde_yaml::Deserializer::from_str(url);
+        deserialize_url(deserializer).unwrap()
+    }
+
+    #[test]
+    fn normalize_bare_host() {
+        assert_eq!(site_url("https://x.com").as_str(), "https://x.com/");
+    }
+
+    #[test]
+    fn normalize_root_with_slash() {
+        assert_eq!(site_url("https://x.com/").as_str(), "https://x.com/");
+    }
+
+    #[test]
+    fn normalize_subdirectory() {
+        let url = site_url("https://x.com/blog");
+        assert_eq!(url.as_str(), "https://x.com/blog/");
+
+        let mut post = url.clone();
+        post.path_segments_mut().unwrap().pop_if_empty().extend(&["p", "hello"]);
+        assert_eq!(post.as_str(), "https://x.com/blog/p/hello");
+    }
+
+    #[test]
+    fn reject_non_base() {
+        let deserializer = serde_yaml::Deserializer::from_str("mailto:me@x.com");
+        assert!(deserialize_url(deserializer).is_err());
+    }
+
+    #[test]
+    fn web_manifest_from_metadata() {
+        let meta = IndexMetadata::from_yaml("title: My Blog\nurl: https://x.com/blog\ntheme_color: \"#123456\"", true).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&meta.web_manifest()).unwrap();
+
+        assert_eq!(manifest["name"], "My Blog");
+        assert_eq!(manifest["start_url"], "/blog/");
+        assert_eq!(manifest["theme_color"], "#123456");
+        assert!(manifest.get("description").is_none());
+        assert_eq!(manifest["icons"], serde_json::json!([{ "src": "/blog/public/favicon.png", "sizes": "32x32" }]));
+
+        let yaml = "title: x\nurl: https://x.com/blog/\napp_icons: [{ path: icons/app.png, sizes: 512x512 }]";
+        let meta = IndexMetadata::from_yaml(yaml, true).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&meta.web_manifest()).unwrap();
+        assert_eq!(manifest["icons"], serde_json::json!([{ "src": "/blog/public/icons/app.png", "sizes": "512x512" }]));
+    }
+
+    #[test]
+    fn edit_link_from_template() {
+        let yaml = "title: x\nurl: https://x.com\nedit_url_template: https://github.com/me/blog/edit/main/posts/{id}.md";
+        let meta = IndexMetadata::from_yaml(yaml, true).unwrap();
+        assert_eq!(meta.edit_link("hello").unwrap().as_str(), "https://github.com/me/blog/edit/main/posts/hello.md");
+
+        let meta = IndexMetadata::from_yaml("title: x\nurl: https://x.com", true).unwrap();
+        assert_eq!(meta.edit_link("hello"), None);
+
+        assert!(IndexMetadata::from_yaml("title: x\nurl: https://x.com\nedit_url_template: https://x.com/edit", true).is_err());
+    }
+
+    #[test]
+    fn nav_links_under_base_path() {
+        let meta = IndexMetadata::from_yaml("title: x\nurl: https://x.com/blog\nnav:\n  random: { label: Surprise me, path: /lucky }", true).unwrap();
+        let links = meta.nav_links()
+            .into_iter()
+            .map(|link| (link.label, link.href))
+            .collect::<Vec<_>>();
+
+        assert_eq!(links, [
+            ("Archive".to_string(), "/blog/archive".to_string()),
+            ("Surprise me".to_string(), "/blog/lucky".to_string()),
+        ]);
     }
 }
@@ -1,7 +1,10 @@
 pub use serde::Deserialize;
+use serde::{de::Error as _, Deserializer};
+use url::Url;
+
 use crate::util::mydatetime::MyDateTime;
 
-use super::{Error, IndexMetadata};
+use super::{index_metadata::deserialize_opt_public_path, Error, IndexMetadata};
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct Metadata {
@@ -9,16 +12,71 @@ pub struct Metadata {
     pub author: Option<String>,
     pub summary: Option<String>,
     pub created: Option<MyDateTime>,
+    /// When the post was last meaningfully changed, which marks it as
+    /// recently updated. File times change with every checkout, so they
+    /// aren't used unless the index has `recently_updated_from_mtime`.
+    pub updated: Option<MyDateTime>,
     #[serde(default)]
     pub highlight: bool,
     #[serde(default)]
+    pub mermaid: bool,
+    #[serde(default)]
     pub tags: Vec<String>,
+    /// Pinned posts are featured at the top of the index.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Pinned posts with a higher weight are shown first.
+    #[serde(default)]
+    pub weight: i32,
+    /// Posts with the same series form a collection.
+    pub series: Option<String>,
+    /// Position of the post within its series.
+    pub series_order: Option<u32>,
+    #[serde(default)]
+    pub comments: bool,
+    /// Drafts are left out of listings and feeds, and can't be viewed,
+    /// unless the server runs with --show-drafts.
+    #[serde(default)]
+    pub draft: bool,
+    /// Image in the `public` directory shown above the article.
+    #[serde(default, deserialize_with = "deserialize_opt_public_path")]
+    pub banner: Option<String>,
+    pub banner_alt: Option<String>,
+    /// Image in the `public` directory shown beside the post in listings.
+    #[serde(default, deserialize_with = "deserialize_opt_public_path")]
+    pub thumbnail: Option<String>,
+    /// Replaces the site's support link on this post, or hides it with
+    /// `coffee: false`.
+    #[serde(default, deserialize_with = "deserialize_coffee")]
+    pub coffee: Option<CoffeeOverride>,
+    /// OpenGraph type of the post, e.g. `video.other`, instead of `article`.
+    #[serde(default, deserialize_with = "deserialize_og_type")]
+    pub og_type: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CoffeeOverride {
+    Hidden,
+    Link(Url),
 }
 
 impl Metadata {
-    pub fn from_yaml<S: AsRef<str>>(yaml: S) -> Result<Self, Error> {
-        let deserializer = serde_yaml::Deserializer::from_str(yaml.as_ref());
-        Ok(Self::deserialize(deserializer)?)
+    pub fn from_yaml<S: AsRef<str>>(yaml: S, strict: bool) -> Result<Self, Error> {
+        super::from_yaml(yaml.as_ref(), strict)
+    }
+
+    /// The support link shown on this post, given the site's
+    pub fn coffee_link<'a>(&'a self, site: Option<&'a Url>) -> Option<&'a Url> {
+        match &self.coffee {
+            None => site,
+            Some(CoffeeOverride::Hidden) => None,
+            Some(CoffeeOverride::Link(url)) => Some(url),
+        }
+    }
+
+    /// The OpenGraph type of the post
+    pub fn og_type(&self) -> &str {
+        self.og_type.as_deref().unwrap_or("article")
     }
 }
 
@@ -29,8 +87,21 @@ impl From<IndexMetadata> for Metadata {
             author: value.author,
             summary: value.summary,
             created: None,
+            updated: None,
             highlight: value.highlight,
+            mermaid: false,
             tags: value.tags,
+            pinned: false,
+            weight: 0,
+            series: None,
+            series_order: None,
+            comments: false,
+            draft: false,
+            banner: None,
+            banner_alt: None,
+            thumbnail: None,
+            coffee: None,
+            og_type: None,
         }
     }
 }
@@ -40,3 +111,85 @@ impl PartialOrd for Metadata {
         self.created.partial_cmp(&other.created)
     }
 }
+
+/// Types defined by the OpenGraph protocol, see <https://ogp.me/#types>
+const OG_TYPES: &[&str] = &[
+    "article",
+    "book",
+    "profile",
+    "website",
+    "music.song",
+    "music.album",
+    "music.playlist",
+    "music.radio_station",
+    "video.movie",
+    "video.episode",
+    "video.tv_show",
+    "video.other",
+];
+
+fn deserialize_og_type<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let og_type = String::deserialize(deserializer)?;
+    if OG_TYPES.contains(&og_type.as_str()) {
+        Ok(Some(og_type))
+    } else {
+        Err(D::Error::custom(format!("Unknown OpenGraph type {og_type:?}")))
+    }
+}
+
+/// Accepts a URL, or a boolean where `true` keeps the site's link.
+fn deserialize_coffee<'de, D>(deserializer: D) -> Result<Option<CoffeeOverride>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Coffee {
+        Show(bool),
+        Link(String),
+    }
+
+    match Coffee::deserialize(deserializer)? {
+        Coffee::Show(true) => Ok(None),
+        Coffee::Show(false) => Ok(Some(CoffeeOverride::Hidden)),
+        Coffee::Link(url) => {
+            let url = Url::parse(&url).map_err(|err| D::Error::custom(format!("{err}")))?;
+            Ok(Some(CoffeeOverride::Link(url)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use url::Url;
+
+    use super::Metadata;
+
+    #[test]
+    fn coffee_override() {
+        let site = Url::parse("https://ko-fi.com/me").unwrap();
+        let coffee = |yaml: &str| {
+            let meta = Metadata::from_yaml(format!("title: x\n{yaml}"), true).unwrap();
+            meta.coffee_link(Some(&site)).map(|url| url.to_string())
+        };
+
+        assert_eq!(coffee("").as_deref(), Some("https://ko-fi.com/me"));
+        assert_eq!(coffee("coffee: true").as_deref(), Some("https://ko-fi.com/me"));
+        assert_eq!(coffee("coffee: false"), None);
+        assert_eq!(coffee("coffee: https://x.com/sponsor").as_deref(), Some("https://x.com/sponsor"));
+    }
+
+    #[test]
+    fn og_type_override() {
+        let og_type = |yaml: &str| {
+            Metadata::from_yaml(format!("title: x\n{yaml}"), true).map(|meta| meta.og_type().to_string())
+        };
+
+        assert_eq!(og_type("").unwrap(), "article");
+        assert_eq!(og_type("og_type: music.song").unwrap(), "music.song");
+        assert!(og_type("og_type: podcast").is_err());
+    }
+}
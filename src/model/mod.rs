@@ -6,10 +6,28 @@ pub use index_metadata::*;
 
 use std::fmt::Display;
 
+use serde::de::DeserializeOwned;
+
 #[derive(Debug)]
 pub enum Error {
     Yaml(serde_yaml::Error),
     Io(std::io::Error),
+    /// Front matter keys that don't correspond to any field
+    UnknownKeys(Vec<String>),
+}
+
+/// Deserializes YAML front matter. With `strict`, keys that don't correspond
+/// to any field are an error instead of being ignored.
+fn from_yaml<T: DeserializeOwned>(yaml: &str, strict: bool) -> Result<T, Error> {
+    let deserializer = serde_yaml::Deserializer::from_str(yaml);
+    let mut unknown = vec![];
+    let value = serde_ignored::deserialize(deserializer, |path| unknown.push(path.to_string()))?;
+
+    if strict && !unknown.is_empty() {
+        Err(Error::UnknownKeys(unknown))
+    } else {
+        Ok(value)
+    }
 }
 
 impl From<serde_yaml::Error> for Error {
@@ -33,6 +51,10 @@ impl From<Error> for std::io::Error {
                 format!("YAML parse error: {yaml}")
             ),
             Error::Io(io) => io,
+            unknown @ Error::UnknownKeys(_) => io::Error::new(
+                ErrorKind::InvalidData,
+                unknown.to_string()
+            ),
         }
     }
 }
@@ -42,6 +64,7 @@ impl Display for Error {
         match self {
             Error::Yaml(err) => err.fmt(f),
             Error::Io(err) => err.fmt(f),
+            Error::UnknownKeys(keys) => write!(f, "Unknown front matter keys: {}", keys.join(", ")),
         }
     }
 }
@@ -1,27 +1,32 @@
 //! `serve` command handler.
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Datelike, Local};
 use clap::{Parser, ValueEnum};
 use dioxus::prelude::*;
 use hyper::{
-    header::{CACHE_CONTROL, CONTENT_TYPE, LAST_MODIFIED, LOCATION, VARY},
-    server::conn::AddrStream,
+    header::{HeaderValue, ACCEPT_RANGES, ALLOW, CACHE_CONTROL, CONNECTION, CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, HOST, LAST_MODIFIED, LINK, LOCATION, VARY},
+    body::HttpBody,
+    server::{accept::Accept, conn::AddrIncoming},
     service::service_fn,
-    Body, Method, Request, Response, StatusCode,
+    Body, Method, Request, Response, StatusCode, Uri, Version,
 };
 use itertools::Itertools;
 use log::{debug, error, info, warn, LevelFilter};
 use std::{
-    convert::Infallible, env, error::Error, io::ErrorKind, net::SocketAddr, num::NonZeroUsize,
-    path::PathBuf, sync::Arc,
+    borrow::Cow, collections::{hash_map::DefaultHasher, HashMap}, convert::Infallible, env, error::Error, fmt, io::ErrorKind,
+    net::SocketAddr, num::{NonZeroU64, NonZeroUsize}, path::{Path, PathBuf}, sync::{Arc, Mutex},
+    hash::{Hash, Hasher}, pin::Pin, string::FromUtf8Error, time::{Duration, Instant},
 };
-use tokio::{fs::File, io::AsyncReadExt, sync::RwLock};
+use tokio::{fs::File, io::AsyncReadExt, sync::{RwLock, Semaphore}};
+use url::Url;
 
 use crate::{
+    model::{ArchiveGroupBy, IndexLayout},
     util::{
-        self, db::{PostContent, PostDb}, has_any_symlinks::HasAnySymlinks, header_ext::HeaderExt
+        self, body, db::{self, IndexNotLoaded, PostContent, PostDb, RssOrder}, extra_headers::{ExtraHeaders, RouteClass}, git_pull, legacy_redirects::LegacyRedirects, has_any_symlinks::HasAnySymlinks, header_ext::{Conditional, HeaderExt}, idle_timeout::IdleTimeout,
+        source::FsContentSource, theme::Theme,
     },
-    view::{self, ArchiveProps, IndexProps, NotFoundProps, PostProps},
+    view::{self, ArchiveGroup, ArchiveProps, ErrorPageProps, ErrorPageSite, Feed, IndexProps, LanguageAlternate, NotFoundProps, PostProps, SeriesProps, SubscribeProps},
 };
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
@@ -31,26 +36,117 @@ pub enum RssContent {
     Always,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum RssMimeType {
+    #[value(name = "application/rss+xml")]
+    ApplicationRss,
+    #[value(name = "application/xml")]
+    ApplicationXml,
+    #[value(name = "text/xml")]
+    TextXml,
+}
+
+impl RssMimeType {
+    fn content_type(&self) -> &'static str {
+        match self {
+            RssMimeType::ApplicationRss => "application/rss+xml; charset=utf-8",
+            RssMimeType::ApplicationXml => "application/xml; charset=utf-8",
+            RssMimeType::TextXml => "text/xml; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum RandomMode {
+    /// Redirect to the randomly chosen post.
+    Redirect,
+    /// Serve the randomly chosen post directly.
+    Inline,
+}
+
 #[derive(Debug, Parser)]
 pub struct Serve {
     /// Root directory of the mdblog project
     #[arg(short, long)]
     directory: Option<PathBuf>,
 
+    /// YAML file mapping Host header values to blog directories, for serving
+    /// several blogs from one process.
+    ///
+    /// Requests for hosts that are not in the file get a 404.
+    #[arg(long, conflicts_with_all = ["directory", "index_path"])]
+    vhosts: Option<PathBuf>,
+
     /// Address and port the server will use.
     #[arg(short, long, default_value = "127.0.0.1:4198")]
     address: SocketAddr,
 
+    /// Close connections that haven't sent a request for this many seconds.
+    ///
+    /// 0 closes every connection after its first response. Idle connections
+    /// are kept open indefinitely by default.
+    #[arg(long)]
+    keep_alive_timeout: Option<u32>,
+
+    /// Most connections served at once. Connections beyond the limit wait
+    /// until another one closes.
+    #[arg(long)]
+    max_connections: Option<NonZeroUsize>,
+
+    /// Markdown file with the index page's content and site metadata.
+    ///
+    /// Defaults to index.md in the root directory.
+    #[arg(long)]
+    index_path: Option<PathBuf>,
+
     /// Post cache time-to-live, in seconds. Lower values result in more frequent updates to served content.
     ///
     /// Values below the default are not recommended for production servers.
     #[arg(short = 'c', long, default_value = "300")]
     cache_ttl: u32,
 
+    /// How long clients may cache pages and feeds, in seconds. Defaults to --cache-ttl.
+    ///
+    /// Independent of --cache-ttl, which only decides how often posts are
+    /// read from disk again.
+    #[arg(long)]
+    max_age: Option<u32>,
+
+    /// How long clients may cache files in the public directory, in seconds.
+    #[arg(long, default_value = "3600")]
+    asset_max_age: u32,
+
+    /// Cache files in the public directory for a year, as immutable, if
+    /// their name has a content hash of at least this many hex digits,
+    /// e.g. styles.3f9a01c2.css. 0, the default, disables this.
+    ///
+    /// Only enable this if every such file really is named after its
+    /// content, since clients never check an immutable file again. Other
+    /// files are cached for --asset-max-age.
+    #[arg(long, default_value = "0")]
+    fingerprint_len: usize,
+
+    /// Seconds that a page may seem to be modified after a client's
+    /// If-Modified-Since and still count as unmodified, for clients whose
+    /// clock is a little behind.
+    ///
+    /// Both times are compared in whole seconds, since HTTP dates have no
+    /// fractions, so 0 is exact.
+    #[arg(long, default_value = "1")]
+    if_modified_since_skew: u32,
+
     /// Maximum number of posts shown on each page of the index.
     #[arg(long, default_value = "10")]
     index_page_len: NonZeroUsize,
 
+    /// Number of recent posts shown on the index. Defaults to --index-page-len.
+    #[arg(long)]
+    index_count: Option<NonZeroUsize>,
+
+    /// Number of posts shown on each page of the archive. Defaults to --index-page-len.
+    #[arg(long)]
+    archive_page_len: Option<NonZeroUsize>,
+
     /// Adjusts the verbosity of the logger.
     #[arg(long, default_value = "warn")]
     pub log_level: LevelFilter,
@@ -58,67 +154,550 @@ pub struct Serve {
     /// When to include post content in RSS feed data
     #[arg(long, default_value = "supports-deltas")]
     rss_content: RssContent,
+
+    /// Maximum number of posts in each feed.
+    #[arg(long, default_value = "25")]
+    feed_items: NonZeroUsize,
+
+    /// Maximum number of posts in the RSS feed. Defaults to --feed-items.
+    ///
+    /// Feed delta responses are limited too, so a client that has been
+    /// away for long may miss some posts. /rss/all has every post.
+    #[arg(long)]
+    rss_items: Option<NonZeroUsize>,
+
+    /// Maximum length of post summaries in the RSS feed, in characters.
+    ///
+    /// Longer summaries are truncated on a word boundary. Unlimited by default.
+    #[arg(long)]
+    rss_summary_len: Option<NonZeroUsize>,
+
+    /// Content-Type of the RSS feed
+    #[arg(long, default_value = "application/rss+xml")]
+    rss_content_type: RssMimeType,
+
+    /// Maximum size of a request body, in bytes. Larger requests are rejected.
+    #[arg(long, default_value = "1048576")]
+    max_body_size: usize,
+
+    /// URL that receives a POST request whenever a post is re-read from disk
+    #[arg(long)]
+    refresh_webhook: Option<Url>,
+
+    /// Run `git pull` in the blog directory at this interval, in seconds.
+    ///
+    /// The blog directory must be a clone of a git repository.
+    #[arg(long)]
+    git_pull_interval: Option<NonZeroU64>,
+
+    /// Serve the list of recent posts at this path, e.g. /posts, instead of on the homepage.
+    ///
+    /// The homepage then shows only the content of index.md.
+    #[arg(long, value_parser = parse_posts_path)]
+    posts_path: Option<String>,
+
+    /// Remove scripts, event handlers and other unsafe markup from rendered posts.
+    ///
+    /// Recommended when posts come from authors you don't fully trust.
+    #[arg(long)]
+    sanitize_html: bool,
+
+    /// Serve the markdown source of each post at /p/{id}.md. Implies --retain-source.
+    #[arg(long)]
+    markdown_source: bool,
+
+    /// Keep the markdown source of posts in memory, not just the rendered HTML.
+    ///
+    /// Roughly doubles the memory used by the post cache.
+    #[arg(long)]
+    retain_source: bool,
+
+    /// Pick translations of posts, stored as {id}.{lang}.md, using the
+    /// Accept-Language header or a `lang` query parameter.
+    #[arg(long)]
+    negotiate_language: bool,
+
+    /// How long clients may cache a 404 page, in seconds.
+    ///
+    /// Keep this short, so a new post's URL doesn't stay "not found" for long.
+    #[arg(long, default_value = "60")]
+    not_found_max_age: u32,
+
+    /// YAML file of extra headers to add to responses, by kind of route.
+    ///
+    /// The kinds are index, post, listing, feed and asset. For example:
+    /// `post: { CDN-Cache-Control: max-age=600 }`
+    #[arg(long)]
+    extra_headers: Option<PathBuf>,
+
+    /// YAML file mapping URLs from a previous blogging platform to post
+    /// ids, which are permanently redirected to the post.
+    ///
+    /// URLs may have a query, like `/?p=123: my-post`, which matches
+    /// requests with those parameters.
+    #[arg(long)]
+    legacy_redirects: Option<PathBuf>,
+
+    /// Find posts regardless of the case of their id, redirecting
+    /// e.g. /p/My-Post to /p/my-post.
+    ///
+    /// Helps with links from sites that change the case of URLs.
+    #[arg(long)]
+    case_insensitive_ids: bool,
+
+    /// Render markdown sent to POST /preview, for live editors.
+    ///
+    /// Only clients on this machine may use it, unless --preview-token is
+    /// set. Behind a reverse proxy every client looks local, so use
+    /// --preview-token there.
+    #[arg(long)]
+    preview: bool,
+
+    /// Allow any client to use POST /preview if it sends this token as
+    /// `Authorization: Bearer <token>`. Implies --preview.
+    #[arg(long)]
+    preview_token: Option<String>,
+
+    /// Query parameters kept in the canonical URL of posts, separated by
+    /// commas. Others, like utm_source, are dropped.
+    ///
+    /// `lang` is always kept with --negotiate-language.
+    #[arg(long, value_delimiter = ',')]
+    canonical_query_params: Vec<String>,
+
+    /// Crawlers kept away from posts, by a case-insensitive part of their
+    /// User-Agent, separated by commas.
+    ///
+    /// robots.txt disallows each of them too, for crawlers that respect it.
+    /// Pass "" to block no crawlers.
+    #[arg(long, value_delimiter = ',', default_value = "GPTBot,ClaudeBot,ImageSift")]
+    blocked_bots: Vec<String>,
+
+    /// Keep the rendered HTML of at most this many posts in memory, dropping
+    /// the least recently viewed first.
+    ///
+    /// Every post's front matter stays cached, for the index, archive and
+    /// feed. Evicted posts are read from disk again when next viewed.
+    #[arg(long)]
+    max_cached_posts: Option<NonZeroUsize>,
+
+    /// Split the sitemap into pages of at most this many URLs, listed by
+    /// /sitemap_index.xml.
+    ///
+    /// Search engines accept at most 50000 URLs per sitemap.
+    #[arg(long, default_value = "50000")]
+    sitemap_max_urls: NonZeroUsize,
+
+    /// Treat posts marked `draft: true` like any other post, e.g. on a
+    /// staging server. Otherwise drafts are hidden.
+    #[arg(long)]
+    show_drafts: bool,
+
+    /// How the /random route responds
+    #[arg(long, default_value = "redirect")]
+    random_mode: RandomMode,
+
+    /// Treat unknown front matter keys as errors instead of ignoring them.
+    ///
+    /// Useful for catching typos like `tittle:`.
+    #[arg(long)]
+    strict_frontmatter: bool,
+
+    /// Directory containing replacements for the bundled icons and styles.
+    ///
+    /// Recognizes rss-icon.svg, coffee.svg, styles.css and favicon.svg. Missing files fall back to the defaults.
+    #[arg(long)]
+    theme_dir: Option<PathBuf>,
+
+    /// Log a warning when a rendered page is larger than this many bytes.
+    ///
+    /// Helps to catch accidentally huge posts. The page is served anyway.
+    #[arg(long)]
+    page_size_warning: Option<NonZeroUsize>,
 }
 
 struct Server {
     db: PostDb,
     // address: SocketAddr,
-    index_page_len: usize,
+    index_count: usize,
+    archive_page_len: usize,
     rss_content: RssContent,
+    rss_summary_len: Option<usize>,
+    rss_items: usize,
+    rss_content_type: RssMimeType,
+    random_mode: RandomMode,
+    max_body_size: usize,
     public_dir: PathBuf,
+    theme: Arc<Theme>,
+    /// The stylesheet's link, and when it was last checked for changes
+    styles_href: Mutex<(String, Instant)>,
+    /// How often the stylesheet is checked for changes, like posts are
+    cache_ttl: Duration,
+    posts_path: Option<String>,
+    markdown_source: bool,
+    negotiate_language: bool,
+    not_found_max_age: u32,
+    max_age: u32,
+    asset_max_age: u32,
+    fingerprint_len: usize,
+    if_modified_since_skew: u32,
+    extra_headers: ExtraHeaders,
+    legacy_redirects: LegacyRedirects,
+    case_insensitive_ids: bool,
+    preview: bool,
+    preview_token: Option<String>,
+    canonical_query_params: Vec<String>,
+    sitemap_max_urls: usize,
+    blocked_bots: Vec<String>,
+    page_size_warning: Option<usize>,
 }
 
 const ROBOTS_TXT: &str = include_str!("res/robots.txt");
 const BOTS: &str = include_str!("res/bots.txt");
+const DEFAULT_FAVICON: &[u8] = include_bytes!("res/favicon.png");
 
 impl Serve {
     pub fn directory(&self) -> Result<PathBuf, std::io::Error> {
         self.directory
             .as_ref()
-            .map_or_else(|| env::current_dir(), |path| dunce::canonicalize(path))
+            .map_or_else(env::current_dir, dunce::canonicalize)
     }
 
-    fn into_server(self) -> Result<Server, Box<dyn Error>> {
-        let dir = self.directory()?;
+    fn make_server(&self, dir: &Path, theme: &Arc<Theme>) -> Result<Server, Box<dyn Error>> {
         let posts_dir = dir.join("posts");
         let public_dir = dir.join("public");
 
-        let db = PostDb::new(posts_dir, self.cache_ttl)?;
+        let index_path = self.index_path.clone().unwrap_or_else(|| dir.join("index.md"));
+
+        let source = FsContentSource::new(posts_dir, index_path)?;
+        let mut db = PostDb::new(Box::new(source), self.cache_ttl);
+        db.set_strict_frontmatter(self.strict_frontmatter);
+        db.set_sanitize_html(self.sanitize_html);
+        db.set_retain_source(self.retain_source || self.markdown_source);
+        if let Some(url) = &self.refresh_webhook {
+            db.set_refresh_webhook(url.clone());
+        }
+        db.set_show_drafts(self.show_drafts);
+        db.set_translations(self.negotiate_language);
+        if let Some(max) = self.max_cached_posts {
+            db.set_max_cached_posts(max.into());
+        }
+
+        let styles_href = styles_href(theme, &public_dir);
 
         let server = Server {
             db,
-            index_page_len: self.index_page_len.into(),
+            index_count: self.index_count.unwrap_or(self.index_page_len).into(),
+            archive_page_len: self.archive_page_len.unwrap_or(self.index_page_len).into(),
             public_dir,
             rss_content: self.rss_content,
+            rss_summary_len: self.rss_summary_len.map(|len| len.into()),
+            rss_items: self.rss_items.unwrap_or(self.feed_items).into(),
+            rss_content_type: self.rss_content_type,
+            random_mode: self.random_mode,
+            max_body_size: self.max_body_size,
+            theme: theme.clone(),
+            styles_href: Mutex::new((styles_href, Instant::now())),
+            cache_ttl: Duration::from_secs(self.cache_ttl.into()),
+            posts_path: self.posts_path.clone(),
+            markdown_source: self.markdown_source,
+            negotiate_language: self.negotiate_language,
+            not_found_max_age: self.not_found_max_age,
+            max_age: self.max_age.unwrap_or(self.cache_ttl),
+            asset_max_age: self.asset_max_age,
+            fingerprint_len: self.fingerprint_len,
+            if_modified_since_skew: self.if_modified_since_skew,
+            case_insensitive_ids: self.case_insensitive_ids,
+            preview: self.preview || self.preview_token.is_some(),
+            preview_token: self.preview_token.clone(),
+            sitemap_max_urls: self.sitemap_max_urls.into(),
+            canonical_query_params: self.canonical_query_params.clone(),
+            // An empty list is given as ""
+            blocked_bots: self.blocked_bots.iter().filter(|bot| !bot.is_empty()).cloned().collect(),
+            page_size_warning: self.page_size_warning.map(|size| size.into()),
+            extra_headers: match &self.extra_headers {
+                Some(path) => ExtraHeaders::load(path)?,
+                None => ExtraHeaders::default(),
+            },
+            legacy_redirects: match &self.legacy_redirects {
+                Some(path) => LegacyRedirects::load(path)?,
+                None => LegacyRedirects::default(),
+            },
         };
         Ok(server)
     }
 
+    /// Blog directories, keyed by the host they are served on. A single blog
+    /// is served on any host.
+    fn directories(&self) -> Result<HashMap<Option<String>, PathBuf>, Box<dyn Error>> {
+        let Some(vhosts) = &self.vhosts else {
+            return Ok(HashMap::from([(None, self.directory()?)]));
+        };
+
+        let config = std::fs::read_to_string(vhosts)
+            .map_err(|err| format!("Reading {vhosts:?}: {err}"))?;
+        let config: HashMap<String, PathBuf> = serde_yaml::from_str(&config)
+            .map_err(|err| format!("Parsing {vhosts:?}: {err}"))?;
+
+        // Relative paths are relative to the vhosts file
+        let base = vhosts.parent().unwrap_or(Path::new(""));
+
+        config
+            .into_iter()
+            .map(|(host, dir)| {
+                let dir = dunce::canonicalize(base.join(&dir))
+                    .map_err(|err| format!("Blog directory {dir:?} for {host}: {err}"))?;
+                Ok((Some(host.to_lowercase()), dir))
+            })
+            .collect()
+    }
+
+    /// Loads every blog given by the arguments, without listening for
+    /// requests, so that another program's HTTP server can serve them.
+    pub fn handler(&self) -> Result<Handler, Box<dyn Error>> {
+        let theme = match &self.theme_dir {
+            Some(dir) => Theme::load(dir)?,
+            None => Theme::default(),
+        };
+        let theme = Arc::new(theme);
+
+        let mut sites = HashMap::new();
+        for (host, dir) in self.directories()? {
+            let server = self.make_server(&dir, &theme)?;
+            info!("Serving {dir:?} on {}", host.as_deref().unwrap_or("any host"));
+            sites.insert(host, Arc::new(RwLock::new(server)));
+        }
+
+        Ok(Handler(Arc::new(sites)))
+    }
+
     pub async fn run(self) -> Result<(), Box<dyn Error>> {
-        let address = self.address.clone();
-        let server = self.into_server()?;
-        let server = Arc::from(RwLock::new(server));
+        let address = self.address;
+
+        if let Some(interval) = self.git_pull_interval {
+            let interval = Duration::from_secs(interval.into());
+            for (_, dir) in self.directories()? {
+                tokio::spawn(git_pull::pull_periodically(dir, interval));
+            }
+        }
+
+        let handler = self.handler()?;
+        let connections = self.max_connections.map(|max| Arc::new(Semaphore::new(max.into())));
 
-        let make_service = hyper::service::make_service_fn(|conn: &AddrStream| {
+        let make_service = hyper::service::make_service_fn(|conn: &IdleTimeout| {
             let address = conn.remote_addr();
 
-            let server = server.clone();
+            let handler = handler.clone();
+            let connections = connections.clone();
 
-            let service =
-                service_fn(move |request| Server::handle(server.clone(), address, request));
+            async move {
+                // Held until the connection closes and drops the service
+                let permit = match connections {
+                    Some(connections) => Some(connections.acquire_owned().await.expect("never closed")),
+                    None => None,
+                };
 
-            async move { Ok::<_, Infallible>(service) }
+                let service = service_fn(move |request| {
+                    let _permit = &permit;
+                    handler.clone().handle(address, request)
+                });
+
+                Ok::<_, Infallible>(service)
+            }
         });
 
         info!("Listening on http://{}", address);
 
-        hyper::Server::bind(&address).serve(make_service).await?;
+        let timeout = self.keep_alive_timeout
+            .filter(|&timeout| timeout > 0)
+            .map(|timeout| Duration::from_secs(timeout.into()));
+        let mut incoming = AddrIncoming::bind(&address)?;
+        let incoming = hyper::server::accept::poll_fn(move |cx| {
+            Pin::new(&mut incoming)
+                .poll_accept(cx)
+                .map_ok(|conn| IdleTimeout::new(conn, timeout))
+        });
+
+        hyper::Server::builder(incoming)
+            .http1_keepalive(self.keep_alive_timeout != Some(0))
+            .serve(make_service)
+            .await?;
 
         Ok(())
     }
 }
 
+fn parse_posts_path(path: &str) -> Result<String, String> {
+    let is_valid = path.len() > 1
+        && path.starts_with('/')
+        && !path.contains(['?', '#'])
+        && !path.ends_with('/');
+
+    if is_valid {
+        Ok(path.to_string())
+    } else {
+        Err("expected an absolute path like /posts".to_string())
+    }
+}
+
+/// Whether the file name at the end of `path` has a part of at least
+/// `min_len` hex digits besides its extension, like a build tool's content
+/// hash in `app-3f9a01c2.js`. A part must have a letter, so that dates and
+/// times like `IMG_20231225_143012.jpg` aren't mistaken for hashes.
+fn is_fingerprinted(path: &str, min_len: usize) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let Some((stem, _extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+
+    min_len > 0 && stem
+        .split(['.', '-', '_'])
+        .any(|part| {
+            part.len() >= min_len
+                && part.bytes().all(|b| b.is_ascii_hexdigit())
+                && part.bytes().any(|b| b.is_ascii_alphabetic())
+        })
+}
+
+/// Link to the stylesheet with a `v` parameter that changes whenever the
+/// stylesheet does, so clients don't keep using a stale copy.
+fn styles_href(theme: &Theme, public_dir: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    match &theme.styles {
+        Some(styles) => styles.hash(&mut hasher),
+        None => match std::fs::metadata(public_dir.join("styles.css")).and_then(|m| m.modified()) {
+            Ok(modified) => modified.hash(&mut hasher),
+            Err(_) => return "/public/styles.css".to_string(),
+        },
+    }
+    format!("/public/styles.css?v={:016x}", hasher.finish())
+}
+
+/// Why a request failed, which decides the response's status code.
+#[derive(Debug)]
+enum ServeError {
+    NotFound,
+    /// Something about the request itself was wrong
+    BadRequest(String),
+    /// The index file hasn't loaded yet, so there is no site to show
+    IndexNotLoaded,
+    Internal(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for ServeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServeError::NotFound => write!(f, "not found"),
+            ServeError::BadRequest(message) => write!(f, "{message}"),
+            ServeError::IndexNotLoaded => IndexNotLoaded.fmt(f),
+            ServeError::Internal(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for ServeError {}
+
+impl From<std::io::Error> for ServeError {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            ErrorKind::NotFound => Self::NotFound,
+            ErrorKind::InvalidInput => Self::BadRequest(value.to_string()),
+            _ => Self::Internal(value.into()),
+        }
+    }
+}
+
+impl From<hyper::http::Error> for ServeError {
+    fn from(value: hyper::http::Error) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+impl From<IndexNotLoaded> for ServeError {
+    fn from(_: IndexNotLoaded) -> Self {
+        Self::IndexNotLoaded
+    }
+}
+
+impl From<body::Error> for ServeError {
+    fn from(value: body::Error) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+impl From<FromUtf8Error> for ServeError {
+    fn from(value: FromUtf8Error) -> Self {
+        Self::BadRequest(value.to_string())
+    }
+}
+
+impl From<Box<dyn Error>> for ServeError {
+    fn from(value: Box<dyn Error>) -> Self {
+        // Not Send, so only the message can be kept
+        Self::Internal(value.to_string().into())
+    }
+}
+
+/// Blogs being served, keyed by host. A single blog served on every host is
+/// keyed by `None`.
+type Sites = HashMap<Option<String>, Arc<RwLock<Server>>>;
+
+/// Answers requests for the blogs loaded by [`Serve::handler`].
+#[derive(Clone)]
+pub struct Handler(Arc<Sites>);
+
+impl Handler {
+    pub async fn handle(
+        self,
+        client_addr: SocketAddr,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, hyper::http::Error> {
+        // HTTP/1.0 connections close after each response unless the client
+        // asks otherwise, which some clients only notice from the header
+        let close = req.version() == Version::HTTP_10
+            && !req.headers().get(CONNECTION).is_some_and(|conn| conn.as_bytes().eq_ignore_ascii_case(b"keep-alive"));
+
+        let mut response = Server::route(self.0, client_addr, req).await?;
+        if close {
+            response.headers_mut().insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+        Ok(response)
+    }
+}
+
 impl Server {
+    /// Picks the blog for the request's host, then handles the request.
+    async fn route(
+        sites: Arc<Sites>,
+        client_addr: SocketAddr,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, hyper::http::Error> {
+        let server = sites.get(&None).or_else(|| {
+            let host = req.headers()
+                .get(HOST)
+                .and_then(|host| host.to_str().ok())
+                .or_else(|| req.uri().host())?;
+
+            // Ignore the port, if there is one
+            let host = match host.rsplit_once(':') {
+                Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+                _ => host,
+            };
+
+            sites.get(&Some(host.to_lowercase()))
+        });
+
+        match server {
+            Some(server) => Server::handle(server.clone(), client_addr, req).await,
+            None => {
+                debug!("{client_addr} {} {:?}: unknown host", req.method(), req.uri());
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::empty())
+            }
+        }
+    }
+
     async fn handle(
         server: Arc<RwLock<Server>>,
         client_addr: SocketAddr,
@@ -127,8 +706,71 @@ impl Server {
         debug!("{client_addr} {} {:?}", req.method(), req.uri());
 
         let req_uri = req.uri().path();
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            let max_body_size = server.read().await.max_body_size;
+            let content_length = req.headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|len| len.to_str().ok())
+                .and_then(|len| len.parse::<u64>().ok());
+
+            if content_length.is_some_and(|len| len > max_body_size as u64) {
+                return Response::builder()
+                    .status(StatusCode::PAYLOAD_TOO_LARGE)
+                    .body(Body::empty());
+            }
+        }
+
+        let is_posts_path = server.read().await.posts_path.as_deref() == Some(req_uri);
+        let route_class = RouteClass::of(req_uri, is_posts_path);
+        let is_tag_feed = req_uri.starts_with("/tags/") && req_uri.ends_with("/rss");
+        let is_feed = req_uri == "/rss" || req_uri == "/rss/all" || is_tag_feed;
+
+        // A read-only blog has nothing to POST to, besides previews, so other
+        // methods are usually someone probing for weaknesses
+        let is_read = method == Method::GET || method == Method::HEAD;
+        let is_preview = method == Method::POST && req_uri == "/preview";
+        if !is_read && !is_preview {
+            let is_get_route = route_class.is_some()
+                || req_uri.to_lowercase() == "/robots.txt"
+                || ["/opensearch.xml", "/manifest.webmanifest"].contains(&req_uri)
+                || (req_uri.starts_with("/sitemap") && req_uri.ends_with(".xml"));
+
+            if is_get_route {
+                info!("{client_addr} {method} {req_uri}: method not allowed");
+                return Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header(ALLOW, "GET, HEAD")
+                    .body(Body::empty());
+            }
+            info!("{client_addr} {method} {req_uri}: no such route, possibly a probe");
+        }
+
+        let legacy_location = if is_read {
+            let server = server.read().await;
+            server.legacy_redirects.lookup(req_uri, req.uri().query()).map(|id| format!("/p/{id}"))
+        } else {
+            None
+        };
 
-        let result = if req.method() == Method::GET && (req_uri == "/" || req_uri == "/rss" || req_uri.starts_with("/archive")) {
+        let result = if let Some(location) = legacy_location {
+            Self::redirect_permanent(&location)
+        } else if is_read && (req_uri == "/index" || req_uri == "/p/index") {
+            // The index is stored like a post, but its only URL is /
+            Self::redirect_permanent("/")
+        } else if is_read && !req_uri.starts_with("/public/") && req_uri.ends_with("/index.html") {
+            // Left over from static site generators, e.g. /p/foo/index.html
+            // With leading slashes collapsed, since a Location like //evil.com
+            // or /\evil.com would send browsers to another site
+            let path = req_uri.strip_suffix("index.html").unwrap();
+            let path = format!("/{}", path.trim_start_matches(['/', '\\']).trim_end_matches('/'));
+            match req.uri().query() {
+                Some(query) => Self::redirect_permanent(&format!("{path}?{query}")),
+                None => Self::redirect_permanent(&path),
+            }
+        } else if is_read && (is_posts_path || req_uri == "/" || is_feed || req_uri.starts_with("/archive") || req_uri.starts_with("/series/") || req_uri == "/subscribe") {
             let index = {
                 let mut server = server.write().await;
                 server
@@ -140,71 +782,214 @@ impl Server {
 
             match index {
                 Ok(index) => {
-                    if req_uri == "/rss" {
+                    if is_posts_path {
+                        server.read().await.posts(req, index).await
+                    } else if is_feed {
                         server.read().await.rss(req).await
                     } else if req_uri == "/" {
                         server.read().await.index(req, index).await
+                    } else if req_uri.starts_with("/series/") {
+                        server.read().await.series(req, index).await
+                    } else if req_uri == "/subscribe" {
+                        server.read().await.subscribe(req, index)
                     } else {
                         server.read().await.archive(req, index).await
                     }
                 }
+                // Rather than a feed or page with no site title or URL
+                Err(_) if server.read().await.db.site().is_err() => Err(ServeError::IndexNotLoaded),
                 Err(err) => Err(err.into()),
             }
-        } else if req.method() == Method::GET && req_uri.starts_with("/p/") {
-            if Server::is_stupid_bot(&req) {
+        } else if is_read && req_uri.starts_with("/p/") {
+            if server.read().await.is_stupid_bot(&req) {
                 return Response::builder()
                     .status(StatusCode::OK)
                     .body(Body::from(BOTS));
             }
 
-            let post = {
-                let id = req_uri.split('/').nth(2).unwrap_or("");
-                let id = id.replace('.', "");
+            let id = req_uri.split('/').nth(2).unwrap_or("");
+            let (id, is_source) = match id.strip_suffix(".md") {
+                Some(id) if server.read().await.markdown_source => (id, true),
+                _ => (id, false),
+            };
+
+            let id = id.to_string();
+
+            let canonical = {
                 let mut server = server.write().await;
+                if server.case_insensitive_ids {
+                    // Finding posts by their lowercase id needs a list of every post
+                    if let Err(err) = server.db.refresh_index(true).await {
+                        error!("While refreshing index: {err}")
+                    }
+                    server.db.canonical_id(&id).filter(|canonical| *canonical != id).map(|c| c.to_string())
+                } else {
+                    None
+                }
+            };
+
+            if let Some(canonical) = canonical {
+                let extension = if is_source { ".md" } else { "" };
+                let location = match req.uri().query() {
+                    Some(query) => format!("/p/{canonical}{extension}?{query}"),
+                    None => format!("/p/{canonical}{extension}"),
+                };
+                Self::redirect_permanent(&location)
+            } else {
+                let (post, lang) = {
+                    let mut server = server.write().await;
+
+                    if let Err(err) = server.db.refresh_index(false).await {
+                        error!("While refreshing index: {err}")
+                    }
+
+                    let (id, lang) = if server.negotiate_language {
+                        let (id, lang) = server.db.resolve_variant(&id, &Self::preferred_languages(&req)).await;
+                        (id, Some(lang))
+                    } else {
+                        (id, None)
+                    };
+
+                    let post = server
+                        .db
+                        .refresh(&id)
+                        .await
+                        .map(|post| post.to_post_content());
 
-                if let Err(err) = server.db.refresh_index(false).await {
-                    error!("While refreshing index: {err}")
+                    (post, lang)
+                };
+
+                match post {
+                    Ok(post) if post.metadata.draft && !server.read().await.db.show_drafts() => {
+                        Err(ServeError::NotFound)
+                    }
+                    Ok(post) if is_source => {
+                        let server = server.read().await;
+                        server.post_source(req, post).await
+                    }
+                    Ok(post) => {
+                        let server = server.read().await;
+                        server.post(req, post, lang.as_deref()).await
+                    }
+                    Err(err) => Err(err.into()),
                 }
+            }
+        } else if is_read && req_uri == "/opensearch.xml" {
+            let index = server.write().await.db.refresh_index(false).await.map(|_| ());
 
-                server
-                    .db
-                    .refresh(&id)
-                    .await
-                    .map(|post| post.to_post_content())
+            match index {
+                Ok(()) => server.read().await.opensearch(),
+                Err(err) => Err(err.into()),
+            }
+        } else if is_read && req_uri == "/manifest.webmanifest" {
+            let index = server.write().await.db.refresh_index(false).await.map(|_| ());
+
+            match index {
+                Ok(()) => server.read().await.web_manifest(),
+                Err(err) => Err(err.into()),
+            }
+        } else if is_read && req_uri == "/posts.json" {
+            let index = server.write().await.db.refresh_index(true).await.map(|_| ());
+
+            match index {
+                Ok(()) => server.read().await.posts_json(&req),
+                Err(err) => Err(err.into()),
+            }
+        } else if is_read && req_uri.starts_with("/sitemap") && req_uri.ends_with(".xml") {
+            let index = server.write().await.db.refresh_index(true).await.map(|_| ());
+
+            match index {
+                Ok(()) => server.read().await.sitemap(&req, req_uri),
+                Err(err) => Err(err.into()),
+            }
+        } else if req.method() == Method::POST && req_uri == "/preview" {
+            let index = server.write().await.db.refresh_index(false).await.map(|_| ());
+
+            match index {
+                Ok(()) => server.read().await.preview(req, client_addr).await,
+                Err(err) => Err(err.into()),
+            }
+        } else if is_read && req_uri.starts_with("/random") {
+            let (id, is_evicted) = {
+                let server = server.read().await;
+                let id = server.db.get_random_id().map(|id| id.to_string());
+                let is_evicted = server.random_mode == RandomMode::Inline
+                    && id.as_deref().and_then(|id| server.db.get(id)).is_some_and(|post| post.body().is_none());
+                (id, is_evicted)
             };
 
-            match post {
-                Ok(post) => {
-                    let server = server.read().await;
-                    server.post(req, post).await
-                }
+            // Only a post whose body was evicted needs the write lock, to be read again
+            let refreshed = match &id {
+                Some(id) if is_evicted => server.write().await.db.refresh(id).await.map(|_| ()),
+                _ => Ok(()),
+            };
+
+            match refreshed {
+                Ok(()) => server.read().await.random(req, id).await,
                 Err(err) => Err(err.into()),
             }
-        } else if req.method() == Method::GET && req_uri.starts_with("/random") {
-            server.read().await.random(req).await
-        } else if req.method() == Method::GET && req_uri.starts_with("/public/") {
+        } else if is_read && req_uri.starts_with("/public/") {
             let server = server.read().await;
             server.public(req).await
-        } else if req.method() == Method::GET && req_uri.to_lowercase().as_str() == "/robots.txt" {
-            Self::robots()
+        } else if is_read && req_uri.to_lowercase().as_str() == "/robots.txt" {
+            server.read().await.robots()
         } else {
             let server = server.read().await;
             server.not_found(req).await
         };
 
-        match result {
-            Ok(ok) => Ok(ok),
-            Err(err) => {
-                let response = Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(Body::from(format!("error: {err}")))?;
-                Ok(response)
+        let mut response = match result {
+            Ok(ok) => ok,
+            Err(ServeError::NotFound) => match server.read().await.not_found_page(method.clone(), &uri) {
+                Ok(response) => response,
+                Err(err) => {
+                    error!("{method} {uri}: {err}");
+                    return server.read().await.error_page();
+                }
+            },
+            Err(err @ ServeError::BadRequest(_)) => {
+                return Self::error_response(StatusCode::BAD_REQUEST, err);
             }
+            Err(err @ ServeError::IndexNotLoaded) => {
+                warn!("{method} {uri}: {err}");
+                return Self::error_response(StatusCode::SERVICE_UNAVAILABLE, err);
+            }
+            Err(err @ ServeError::Internal(_)) => {
+                error!("{method} {uri}: {err}");
+                return server.read().await.error_page();
+            }
+        };
+
+        if let Some(class) = route_class {
+            server.read().await.extra_headers.apply(class, response.headers_mut());
+        }
+
+        // HEAD is routed like GET, and answered with the same headers
+        if method == Method::HEAD {
+            if let Some(len) = response.body().size_hint().exact() {
+                if !response.headers().contains_key(CONTENT_LENGTH) {
+                    response.headers_mut().insert(CONTENT_LENGTH, len.into());
+                }
+            }
+            *response.body_mut() = Body::empty();
         }
+
+        Ok(response)
     }
 
-    async fn public(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error>> {
+    async fn public(&self, req: Request<Body>) -> Result<Response<Body>, ServeError> {
         let subpath = req.uri().path().strip_prefix("/public/").unwrap();
+
+        if subpath == "styles.css" {
+            if let Some(styles) = &self.theme.styles {
+                return Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CACHE_CONTROL, format!("max-age={}", self.asset_max_age))
+                    .header(CONTENT_TYPE, "text/css; charset=utf-8")
+                    .body(Body::from(styles.clone()))?);
+            }
+        }
+
         let path = self.public_dir.join(subpath);
 
         let is_suspicious = path
@@ -229,7 +1014,14 @@ impl Server {
         let mut file = match File::open(&path).await {
             Ok(file) => file,
             Err(err) => {
-                if err.kind() == ErrorKind::NotFound {
+                if err.kind() == ErrorKind::NotFound && subpath == "favicon.png" {
+                    // Every page links the favicon, so blogs without one get ours
+                    return Ok(Response::builder()
+                        .status(StatusCode::OK)
+                        .header(CACHE_CONTROL, format!("max-age={}", self.asset_max_age))
+                        .header(CONTENT_TYPE, "image/png")
+                        .body(Body::from(DEFAULT_FAVICON))?);
+                } else if err.kind() == ErrorKind::NotFound {
                     return self.not_found(req).await;
                 } else {
                     return Err(err.into());
@@ -237,34 +1029,56 @@ impl Server {
             }
         };
 
-        let post_last_modified = file
-            .metadata()
-            .await
-            .and_then(|meta| meta.modified())
-            .map(|lm| DateTime::<Local>::from(lm))
-            .ok();
+        let cache_control = if is_fingerprinted(subpath, self.fingerprint_len) {
+            "public, max-age=31536000, immutable".to_string()
+        } else {
+            format!("max-age={}", self.asset_max_age)
+        };
+
+        let metadata = file.metadata().await?;
+        let resp = Response::builder()
+            .header(CACHE_CONTROL, cache_control)
+            .header(ACCEPT_RANGES, "bytes");
 
-        let cache_valid = post_last_modified
-            .as_ref()
-            .map_or(false, |timestamp| req.headers().is_cache_valid(timestamp));
+        // Without a modification time there is nothing to validate against
+        let Ok(last_modified) = metadata.modified().map(DateTime::<Local>::from) else {
+            let mut body = vec![];
+            file.read_to_end(&mut body).await?;
+            return Ok(resp.status(StatusCode::OK).body(Body::from(body))?);
+        };
 
-        if cache_valid {
-            return Ok(Response::builder()
-                .status(StatusCode::NOT_MODIFIED)
-                .body(Body::empty())?);
-        }
+        let etag = format!("\"{:x}-{:x}\"", last_modified.timestamp(), metadata.len());
+        let resp = resp
+            .header(LAST_MODIFIED, last_modified.to_rfc2822())
+            .header(ETAG, &etag);
+
+        let range = match req.headers().conditional(Some(&etag), &last_modified, self.if_modified_since_skew) {
+            Conditional::NotModified => {
+                return Ok(resp.status(StatusCode::NOT_MODIFIED).body(Body::empty())?);
+            }
+            Conditional::Partial(range) => match range.resolve(metadata.len()) {
+                Some(range) => Some(range),
+                None => {
+                    return Ok(resp
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(CONTENT_RANGE, format!("bytes */{}", metadata.len()))
+                        .body(Body::empty())?);
+                }
+            },
+            Conditional::Full => None,
+        };
 
         let mut body = vec![];
         file.read_to_end(&mut body).await?;
 
-        let resp = Response::builder()
-            .status(StatusCode::OK)
-            .header(CACHE_CONTROL, "max-age=3600");
-
-        let resp = if let Some(lm) = post_last_modified {
-            resp.header(LAST_MODIFIED, lm.to_rfc2822())
-        } else {
-            resp
+        let resp = match range {
+            Some((first, last)) if last < body.len() as u64 => {
+                let content_range = format!("bytes {first}-{last}/{}", body.len());
+                body = body[first as usize..=last as usize].to_vec();
+                resp.status(StatusCode::PARTIAL_CONTENT)
+                    .header(CONTENT_RANGE, content_range)
+            }
+            _ => resp.status(StatusCode::OK),
         };
 
         Ok(resp.body(Body::from(body))?)
@@ -274,28 +1088,77 @@ impl Server {
         &self,
         req: Request<Body>,
         content: PostContent,
-    ) -> Result<Response<Body>, Box<dyn Error>> {
-        if req.headers().is_cache_valid(&self.db.index_updated()) {
+    ) -> Result<Response<Body>, ServeError> {
+        // Feed readers given the bare domain find the feed
+        if req.headers().prefers_feed() {
             return Ok(Response::builder()
-                .status(StatusCode::NOT_MODIFIED)
+                .status(StatusCode::FOUND)
+                .header(LOCATION, "/rss")
+                .header(VARY, "Accept")
                 .body(Body::empty())?);
         }
 
-        let canonical_url = self.db.site_url().clone();
+        // With a separate list of posts, the homepage is just the intro
+        let layout = if self.posts_path.is_some() {
+            IndexLayout::IntroOnly
+        } else {
+            self.db.index_layout()?
+        };
 
-        let coffee_link = self.db.coffee_url().map(|c| c.to_owned());
+        let canonical_url = self.db.site_url()?.clone();
 
-        let site_title_short = self.db.site_title_short().to_owned();
+        let mut resp = self.render_index(req, content, layout, canonical_url)?;
+        resp.headers_mut().insert(VARY, HeaderValue::from_static("HX-Request, Accept"));
+        Ok(resp)
+    }
 
-        let last_modified = content.last_modified().to_rfc2822();
+    /// The list of recent posts, when it is separate from the homepage
+    async fn posts(
+        &self,
+        req: Request<Body>,
+        content: PostContent,
+    ) -> Result<Response<Body>, ServeError> {
+        let mut canonical_url = self.db.site_url()?.clone();
+        canonical_url.set_path(self.posts_path.as_deref().unwrap_or_default());
 
-        let posts = self
-            .db
-            .all_posts()
-            .sorted_by(|a, b| b.cmp_published(a))
-            // .sorted_by_key(|p| p.updated())
+        self.render_index(req, content, IndexLayout::ListOnly, canonical_url)
+    }
+
+    fn render_index(
+        &self,
+        req: Request<Body>,
+        content: PostContent,
+        layout: IndexLayout,
+        canonical_url: Url,
+    ) -> Result<Response<Body>, ServeError> {
+        if req.headers().conditional(None, &self.db.index_updated(), self.if_modified_since_skew) == Conditional::NotModified {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
+        }
+
+        let fragment = req.headers().is_fragment_request();
+        let coffee_link = self.db.coffee_url()?.map(|c| c.to_owned());
+
+        let site_title = self.db.site_title()?.to_owned();
+        let site_title_short = self.db.site_title_short()?.to_owned();
+
+        let last_modified = content.last_modified().to_rfc2822();
+
+        let featured = self
+            .db
+            .pinned_posts()
+            .map(|post| post.to_post_meta())
+            .collect_vec();
+
+        let posts = self
+            .db
+            .content_posts()
+            .filter(|post| !post.metadata().pinned)
+            .sorted_by(|a, b| b.cmp_published(a))
+            // .sorted_by_key(|p| p.updated())
             // .skip(page * self.index_page_len)
-            .take(self.index_page_len)
+            .take(self.index_count)
             .map(|post| post.to_post_meta())
             .collect_vec();
 
@@ -305,89 +1168,351 @@ impl Server {
             view::index,
             IndexProps {
                 posts,
+                featured,
                 content,
                 canonical_url,
+                site_title,
                 site_title_short,
                 coffee_link,
+                layout,
+                nav: self.db.nav_links()?,
+                theme: self.theme.clone(),
+                header_labels: self.db.header_labels()?.clone(),
+                styles_href: self.styles_href(),
+                theme_color: self.db.theme_color()?.map(|color| color.to_string()),
+                color_scheme: self.db.color_scheme()?.map(|scheme| scheme.to_string()),
+                opensearch: self.db.opensearch()?,
+                fragment,
             },
         );
-        let body = util::render_html(vdom, self.db.lang());
+        let body = self.render_page(vdom, self.db.lang()?, fragment, req.uri().path());
 
         Ok(Response::builder()
             .status(StatusCode::OK)
-            .header(CACHE_CONTROL, "max-age=3600")
+            .header(CACHE_CONTROL, format!("max-age={}", self.max_age))
             .header(LAST_MODIFIED, last_modified)
+            .header(VARY, "HX-Request")
             .header(CONTENT_TYPE, "text/html; charset=utf-8")
             .body(Body::from(body))?)
     }
 
-    async fn archive(&self, _req: Request<Body>, index: PostContent) -> Result<Response<Body>, Box<dyn Error>> {
+    async fn archive(&self, req: Request<Body>, index: PostContent) -> Result<Response<Body>, ServeError> {
+        let page = match Self::query_param(&req, "page") {
+            Some(page) => match page.parse::<usize>() {
+                Ok(page) if page > 0 => page,
+                _ => return self.not_found(req).await,
+            },
+            None => 1,
+        };
+
+        let post_count = self.db.content_posts().count();
+        let page_count = std::cmp::max(post_count.div_ceil(self.archive_page_len), 1);
+
+        if page > page_count {
+            return self.not_found(req).await;
+        }
+
+        // Every page is made from the same list of posts, so they all change
+        // when the index does
+        if req.headers().conditional(None, &self.db.index_updated(), self.if_modified_since_skew) == Conditional::NotModified {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
+        }
+
+        // `?sort=oldest` and `?sort=title` help to browse a large archive
+        let sort = Self::query_param(&req, "sort").filter(|sort| sort == "oldest" || sort == "title");
+        let order: fn(&db::Post, &db::Post) -> std::cmp::Ordering = match sort.as_deref() {
+            Some("oldest") => |a, b| a.cmp_published(b),
+            Some("title") => |a, b| {
+                let title = |post: &db::Post| post.metadata().title.to_lowercase();
+                title(a).cmp(&title(b))
+            },
+            _ => |a, b| b.cmp_published(a),
+        };
+
         let posts = self
             .db
-            .all_posts()
-            .sorted_by(|a, b| b.cmp_published(a))
-            .map(|post| post.to_post_meta())
-            .collect_vec();
+            .content_posts()
+            .sorted_by(order)
+            .skip((page - 1) * self.archive_page_len)
+            .take(self.archive_page_len)
+            .map(|post| (post.published().year(), post.to_post_meta()));
+
+        // Years only make sense when the posts are in order of publication
+        let groups = if self.db.archive_group_by()? == ArchiveGroupBy::Year && sort.as_deref() != Some("title") {
+            posts
+                .group_by(|(year, _)| *year)
+                .into_iter()
+                .map(|(year, posts)| ArchiveGroup {
+                    heading: Some(year.to_string()),
+                    posts: posts.map(|(_, post)| post).collect(),
+                })
+                .collect_vec()
+        } else {
+            vec![ArchiveGroup {
+                heading: None,
+                posts: posts.map(|(_, post)| post).collect(),
+            }]
+        };
 
-        let canonical_url = self.db.site_url().clone();
-        let coffee_link = self.db.coffee_url().map(|c| c.to_owned());
-        let site_title_short = self.db.site_title_short().to_owned();
+        let site_url = self.db.site_url()?;
+        let page_url = |page: usize| {
+            let mut url = site_url.clone();
+            url.path_segments_mut()
+                .expect("site_url shall be a base")
+                .pop_if_empty()
+                .push("archive");
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            if let Some(sort) = &sort {
+                query.append_pair("sort", sort);
+            }
+            if page > 1 {
+                query.append_pair("page", &page.to_string());
+            }
+            let query = query.finish();
+            url.set_query(Some(&query).filter(|query| !query.is_empty()).map(|query| query.as_str()));
+            url
+        };
+
+        let canonical_url = page_url(page);
+        let prev_url = (page > 1).then(|| page_url(page - 1));
+        let next_url = (page < page_count).then(|| page_url(page + 1));
+
+        let link = prev_url.iter().map(|url| format!("<{url}>; rel=\"prev\""))
+            .chain(next_url.iter().map(|url| format!("<{url}>; rel=\"next\"")))
+            .join(", ");
+        let coffee_link = self.db.coffee_url()?.map(|c| c.to_owned());
+        let site_title = self.db.site_title()?.to_owned();
+        let site_title_short = self.db.site_title_short()?.to_owned();
         let last_modified = self.db.index_updated().to_rfc2822();
+        let fragment = req.headers().is_fragment_request();
 
         let vdom = VirtualDom::new_with_props(
             view::archive,
             ArchiveProps {
+                groups,
+                metadata: index.metadata,
+                canonical_url,
+                site_title,
+                site_title_short,
+                coffee_link,
+                theme: self.theme.clone(),
+                header_labels: self.db.header_labels()?.clone(),
+                styles_href: self.styles_href(),
+                theme_color: self.db.theme_color()?.map(|color| color.to_string()),
+                color_scheme: self.db.color_scheme()?.map(|scheme| scheme.to_string()),
+                opensearch: self.db.opensearch()?,
+                prev_url,
+                next_url,
+                sorted: sort.is_some(),
+                fragment,
+            },
+        );
+        let body = self.render_page(vdom, self.db.lang()?, fragment, req.uri().path());
+
+        let resp = Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, format!("max-age={}", self.max_age))
+            .header(LAST_MODIFIED, last_modified)
+            .header(VARY, "HX-Request")
+            .header(CONTENT_TYPE, "text/html; charset=utf-8");
+
+        let resp = if link.is_empty() {
+            resp
+        } else {
+            resp.header(LINK, link)
+        };
+
+        Ok(resp.body(Body::from(body))?)
+    }
+
+    async fn series(&self, req: Request<Body>, index: PostContent) -> Result<Response<Body>, ServeError> {
+        let name = req.uri().path().strip_prefix("/series/").unwrap();
+        let series = match percent_encoding::percent_decode_str(name).decode_utf8() {
+            Ok(series) => series.into_owned(),
+            Err(_) => return self.not_found(req).await,
+        };
+
+        let posts = self
+            .db
+            .series_members(&series)
+            .map(|post| post.to_post_meta())
+            .collect_vec();
+
+        if posts.is_empty() {
+            return self.not_found(req).await;
+        }
+
+        let mut canonical_url = self.db.site_url()?.clone();
+        canonical_url.set_path(&util::series_path(&series));
+        let coffee_link = self.db.coffee_url()?.map(|c| c.to_owned());
+        let site_title = self.db.site_title()?.to_owned();
+        let site_title_short = self.db.site_title_short()?.to_owned();
+        let last_modified = self.db.index_updated().to_rfc2822();
+        let fragment = req.headers().is_fragment_request();
+
+        let vdom = VirtualDom::new_with_props(
+            view::series,
+            SeriesProps {
+                series,
                 posts,
                 metadata: index.metadata,
                 canonical_url,
+                site_title,
                 site_title_short,
                 coffee_link,
+                theme: self.theme.clone(),
+                header_labels: self.db.header_labels()?.clone(),
+                styles_href: self.styles_href(),
+                theme_color: self.db.theme_color()?.map(|color| color.to_string()),
+                color_scheme: self.db.color_scheme()?.map(|scheme| scheme.to_string()),
+                opensearch: self.db.opensearch()?,
+                fragment,
             },
         );
-        let body = util::render_html(vdom, self.db.lang());
+        let body = self.render_page(vdom, self.db.lang()?, fragment, req.uri().path());
 
         Ok(Response::builder()
             .status(StatusCode::OK)
-            .header(CACHE_CONTROL, "max-age=3600")
+            .header(CACHE_CONTROL, format!("max-age={}", self.max_age))
             .header(LAST_MODIFIED, last_modified)
+            .header(VARY, "HX-Request")
             .header(CONTENT_TYPE, "text/html; charset=utf-8")
             .body(Body::from(body))?)
     }
 
-    async fn random(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error>> {
-        let id = self
-            .db
-            .get_random_id()
-            .ok_or_else(|| "this blog has no posts!".to_string())
-            .map_err(|e| Box::<dyn Error>::from(e))?;
+    /// A page of every feed, for readers who are new to them
+    fn subscribe(&self, req: Request<Body>, index: PostContent) -> Result<Response<Body>, ServeError> {
+        let site_url = self.db.site_url()?;
+        let url = |path: &[&str]| {
+            let mut url = site_url.clone();
+            url.path_segments_mut()
+                .expect("site_url shall be a base")
+                .pop_if_empty()
+                .extend(path);
+            url
+        };
+
+        let mut feeds = vec![
+            Feed {
+                title: "Latest posts".to_string(),
+                description: format!("The {} newest posts.", self.rss_items),
+                url: url(&["rss"]),
+            },
+            Feed {
+                title: "Every post".to_string(),
+                description: "Every post ever published, for reading the whole blog.".to_string(),
+                url: url(&["rss", "all"]),
+            },
+        ];
+        feeds.extend(self.db.tags().into_iter().map(|tag| Feed {
+            title: format!("Posts tagged {tag}"),
+            description: format!("Only posts about {tag}."),
+            url: url(&["tags", tag, "rss"]),
+        }));
+        feeds.push(Feed {
+            title: "JSON".to_string(),
+            description: "The list of posts as JSON, for scripts rather than feed readers.".to_string(),
+            url: url(&["posts.json"]),
+        });
+
+        let canonical_url = url(&["subscribe"]);
+        let coffee_link = self.db.coffee_url()?.map(|c| c.to_owned());
+        let site_title = self.db.site_title()?.to_owned();
+        let site_title_short = self.db.site_title_short()?.to_owned();
+        let last_modified = self.db.index_updated().to_rfc2822();
+        let fragment = req.headers().is_fragment_request();
+
+        let vdom = VirtualDom::new_with_props(
+            view::subscribe,
+            SubscribeProps {
+                feeds,
+                metadata: index.metadata,
+                canonical_url,
+                site_title,
+                site_title_short,
+                coffee_link,
+                theme: self.theme.clone(),
+                header_labels: self.db.header_labels()?.clone(),
+                styles_href: self.styles_href(),
+                theme_color: self.db.theme_color()?.map(|color| color.to_string()),
+                color_scheme: self.db.color_scheme()?.map(|scheme| scheme.to_string()),
+                opensearch: self.db.opensearch()?,
+                fragment,
+            },
+        );
+        let body = self.render_page(vdom, self.db.lang()?, fragment, req.uri().path());
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, format!("max-age={}", self.max_age))
+            .header(LAST_MODIFIED, last_modified)
+            .header(VARY, "HX-Request")
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(body))?)
+    }
+
+    /// Responds with the post `id`, picked at random, or with the homepage
+    /// if there are no posts to pick from.
+    async fn random(&self, req: Request<Body>, id: Option<String>) -> Result<Response<Body>, ServeError> {
+        let Some(id) = id else {
+            // A new blog has nothing to pick from, but its homepage
+            return Ok(Response::builder()
+                .status(StatusCode::FOUND)
+                .header(CACHE_CONTROL, "no-store")
+                .header(LOCATION, "/")
+                .body(Body::empty())?);
+        };
+
+        let location = format!("/p/{id}");
+
+        if self.random_mode == RandomMode::Redirect {
+            // Rendering the post here would be wasted work, since clients
+            // follow the Location header. A bare link is enough of a fallback.
+            let href = html_escape::encode_double_quoted_attribute(&location);
+            let text = html_escape::encode_text(&location);
+            let body = format!("<a href=\"{href}\">{text}</a>");
+
+            return Ok(Response::builder()
+                .status(StatusCode::FOUND)
+                .header(CACHE_CONTROL, "no-store")
+                .header(LOCATION, location)
+                .header(CONTENT_TYPE, "text/html; charset=utf-8")
+                .body(Body::from(body))?);
+        }
 
         let post = self
             .db
             .get(&id)
-            .ok_or_else(|| "unexpected - random id not valid".to_string())
-            .map_err(|e| Box::<dyn Error>::from(e))?
+            // Deleted since it was picked
+            .ok_or(ServeError::NotFound)?
             .to_post_content();
 
         let last_modified = post.last_modified().to_rfc2822();
 
-        let body = self.render_post(post, &id, req.uri().query())?;
-
-        let location = format!("/p/{id}");
+        // The canonical URL points at the post itself, so search engines
+        // don't treat /random as duplicate content.
+        let body = self.render_post(post, &location, None, None, req.headers().is_fragment_request())?;
 
+        // Each request picks a different post, so caches must never reuse a response
         Ok(Response::builder()
-            .status(StatusCode::FOUND)
-            .header(LOCATION, location)
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, "no-store")
             .header(LAST_MODIFIED, last_modified)
             .header(CONTENT_TYPE, "text/html; charset=utf-8")
             .body(Body::from(body))?)
     }
 
+    /// Renders a post. `lang` is the language of the variant that was
+    /// chosen, when the post was picked by language negotiation.
     async fn post(
         &self,
         req: Request<Body>,
         post: PostContent,
-    ) -> Result<Response<Body>, Box<dyn Error>> {
-        if req.headers().is_cache_valid(&post.last_modified()) {
+        lang: Option<&str>,
+    ) -> Result<Response<Body>, ServeError> {
+        if req.headers().conditional(None, &post.last_modified(), self.if_modified_since_skew) == Conditional::NotModified {
             return Ok(Response::builder()
                 .status(StatusCode::NOT_MODIFIED)
                 .body(Body::empty())?);
@@ -395,31 +1520,101 @@ impl Server {
 
         let last_modified = post.last_modified().to_rfc2822();
 
-        let body = self.render_post(post, req.uri().path(), req.uri().query())?;
+        let fragment = req.headers().is_fragment_request();
+        let body = self.render_post(post, req.uri().path(), req.uri().query(), lang, fragment)?;
 
-        let cache_control = format!("max-age={}", self.db.ttl().as_secs());
+        let cache_control = format!("max-age={}", self.max_age);
 
-        Ok(Response::builder()
+        let resp = Response::builder()
             .status(StatusCode::OK)
             .header(CACHE_CONTROL, cache_control)
             .header(LAST_MODIFIED, last_modified)
             .header(CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(Body::from(body))?)
+            .header(VARY, "HX-Request");
+
+        let resp = match lang {
+            Some(lang) => resp
+                .header(CONTENT_LANGUAGE, lang)
+                .header(VARY, "Accept-Language"),
+            None => resp,
+        };
+
+        Ok(resp.body(Body::from(body))?)
+    }
+
+    async fn post_source(&self, req: Request<Body>, post: PostContent) -> Result<Response<Body>, ServeError> {
+        let Some(source) = post.source.clone() else {
+            return self.not_found(req).await;
+        };
+
+        if req.headers().conditional(None, &post.last_modified(), self.if_modified_since_skew) == Conditional::NotModified {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
+        }
+
+        let cache_control = format!("max-age={}", self.max_age);
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, cache_control)
+            .header(LAST_MODIFIED, post.last_modified().to_rfc2822())
+            .header(CONTENT_TYPE, "text/markdown; charset=utf-8")
+            .body(Body::from(source))?)
     }
 
+    /// Renders a post. `lang` is the language of the post, if it isn't
+    /// in the site's language.
     fn render_post(
         &self,
         post: PostContent,
         path: &str,
         query: Option<&str>,
-    ) -> Result<String, Box<dyn Error>> {
-        let site_title = self.db.site_title().to_string();
-        let mut canonical_url = self.db.site_url().clone();
+        lang: Option<&str>,
+        fragment: bool,
+    ) -> Result<String, ServeError> {
+        let mut canonical_url = self.db.site_url()?.clone();
         canonical_url.set_path(path);
-        canonical_url.set_query(query);
+        canonical_url.set_query(self.canonical_query(query).as_deref());
         let twitter_link = self.db.twitter_link(&post.id)?;
-        let coffee_link = self.db.coffee_url().map(|c| c.to_owned());
-        let site_title_short = self.db.site_title_short().to_owned();
+        let edit_link = self.db.edit_link(&post.id)?;
+        let coffee_link = post.metadata.coffee_link(self.db.coffee_url()?).cloned();
+        let site_title = self.db.site_title()?.to_owned();
+        let site_title_short = self.db.site_title_short()?.to_owned();
+        let series = post
+            .metadata
+            .series
+            .as_deref()
+            .map(|series| {
+                self.db
+                    .series_members(series)
+                    .map(|post| post.to_post_meta())
+                    .collect_vec()
+            })
+            .unwrap_or_default();
+        let comments_script = if post.metadata.comments {
+            self.db.comments_script()?.map(|c| c.to_owned())
+        } else {
+            None
+        };
+        let languages = if self.negotiate_language {
+            self.language_alternates(&post.id)?
+        } else {
+            vec![]
+        };
+        let read_next = self.db.newest_excluding(&post.id);
+        let recently_updated = self.db
+            .recently_updated_window()
+            .is_some_and(|window| post.is_recently_updated(window, self.db.recently_updated_from_mtime()));
+        let stats = if self.db.footer_stats()? {
+            Some(view::SiteStats {
+                post_count: self.db.post_count(),
+                latest: self.db.latest_post_date(),
+            })
+        } else {
+            None
+        };
+        let id = post.id.clone();
 
         let vdom = VirtualDom::new_with_props(
             view::post,
@@ -429,14 +1624,89 @@ impl Server {
                 canonical_url,
                 twitter_link,
                 coffee_link,
+                edit_link,
                 site_title_short,
+                comments_script,
+                series,
+                languages,
+                read_next,
+                recently_updated,
+                stats,
+                fragment,
+                theme: self.theme.clone(),
+                header_labels: self.db.header_labels()?.clone(),
+                styles_href: self.styles_href(),
+                theme_color: self.db.theme_color()?.map(|color| color.to_string()),
+                color_scheme: self.db.color_scheme()?.map(|scheme| scheme.to_string()),
+                opensearch: self.db.opensearch()?,
             },
         );
-        Ok(util::render_html(vdom, self.db.lang()))
+        Ok(self.render_page(vdom, lang.unwrap_or(self.db.lang()?), fragment, &format!("post {id:?}")))
+    }
+
+    /// Renders a whole page, or only its content if client-side navigation
+    /// asked for a fragment. `page` names the page in the warning about its size.
+    fn render_page(&self, vdom: VirtualDom, lang: &str, fragment: bool, page: &str) -> String {
+        let body = if fragment {
+            util::render_fragment(vdom)
+        } else {
+            util::render_html(vdom, lang)
+        };
+
+        if let Some(limit) = self.page_size_warning.filter(|&limit| body.len() > limit) {
+            warn!("Rendered {page} is {} bytes, more than --page-size-warning={limit}", body.len());
+        }
+
+        body
+    }
+
+    /// The parameters of `query` that belong in a canonical URL, so that
+    /// e.g. tracking parameters don't make a page look like several pages.
+    fn canonical_query(&self, query: Option<&str>) -> Option<String> {
+        let params = url::form_urlencoded::parse(query?.as_bytes())
+            .filter(|(key, _)| {
+                self.canonical_query_params.iter().any(|param| param == key)
+                    || (self.negotiate_language && key == "lang")
+            });
+
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        (!query.is_empty()).then_some(query)
+    }
+
+    /// Links to each language of a post, for `hreflang`. Empty if the post
+    /// has no translations.
+    fn language_alternates(&self, id: &str) -> Result<Vec<LanguageAlternate>, ServeError> {
+        let id = db::untranslated_id(id);
+        let translations = self.db.translations(id).collect_vec();
+        if translations.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut url = self.db.site_url()?.clone();
+        url.path_segments_mut()
+            .expect("site_url shall be a base")
+            .pop_if_empty()
+            .extend(&["p", id]);
+
+        let alternate = |lang: &str| {
+            let mut url = url.clone();
+            url.query_pairs_mut().append_pair("lang", lang);
+            // hreflang wants BCP 47 tags, which use hyphens
+            LanguageAlternate { lang: lang.replace('_', "-"), url }
+        };
+
+        let mut languages = vec![alternate(self.db.lang()?)];
+        languages.extend(translations.into_iter().map(alternate));
+        // Without a lang parameter, the post is picked by negotiation
+        languages.push(LanguageAlternate { lang: "x-default".to_string(), url });
+
+        Ok(languages)
     }
 
-    async fn rss(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error>> {
-        if req.headers().is_cache_valid(&self.db.index_updated()) {
+    async fn rss(&self, req: Request<Body>) -> Result<Response<Body>, ServeError> {
+        if req.headers().conditional(None, &self.db.index_updated(), self.if_modified_since_skew) == Conditional::NotModified {
             return Ok(Response::builder()
                 .status(StatusCode::NOT_MODIFIED)
                 .body(Body::empty())?);
@@ -460,49 +1730,1023 @@ impl Server {
             RssContent::SupportsDeltas => deltas_supported,
         };
 
-        let rss = self.db.get_rss(since, include_content, 25).build();
+        // `?sort=updated` resurfaces posts when they are edited
+        let order = match Self::query_param(&req, "sort").as_deref() {
+            Some("updated") => RssOrder::Modified,
+            _ => RssOrder::Published,
+        };
+
+        // /tags/{tag}/rss only has posts with that tag
+        let tag = match req.uri().path().strip_prefix("/tags/").and_then(|path| path.strip_suffix("/rss")) {
+            Some(tag) => {
+                let tag = percent_encoding::percent_decode_str(tag)
+                    .decode_utf8()
+                    .map_err(|_| ServeError::NotFound)?;
+                if !self.db.has_tag(&tag) {
+                    return Err(ServeError::NotFound);
+                }
+                Some(tag)
+            }
+            None => None,
+        };
+
+        // /rss/all is for readers syncing the whole history. Every post is
+        // rendered into it, so it is as large as the blog.
+        let max = if req.uri().path() == "/rss/all" {
+            usize::MAX
+        } else {
+            self.rss_items
+        };
+
+        let rss = self.db.get_rss(since, include_content, self.rss_summary_len, max, order, tag.as_deref()).await?.build();
         let last_modified = self.db.index_updated().to_rfc2822();
 
         debug!("Sending {} items", rss.items.len());
 
-        let cache_control = format!("im, max-age={}", self.db.ttl().as_secs());
+        let cache_control = format!("im, max-age={}", self.max_age);
 
         Ok(Response::builder()
             .status(StatusCode::OK)
             .header(CACHE_CONTROL, cache_control)
             .header(LAST_MODIFIED, last_modified)
-            .header(CONTENT_TYPE, "text/xml; charset=utf-8")
+            .header(CONTENT_TYPE, self.rss_content_type.content_type())
             .header(VARY, "A-IM, If-Modified-Since")
             .body(Body::from(rss.to_string()))?)
     }
 
-    fn robots() -> Result<Response<Body>, Box<dyn Error>> {
+    fn opensearch(&self) -> Result<Response<Body>, ServeError> {
+        // ustack has no /search of its own, so the site has to provide one
+        if !self.db.opensearch()? {
+            return Err(ServeError::NotFound);
+        }
+
+        let last_modified = self.db.index_updated().to_rfc2822();
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, format!("max-age={}", self.asset_max_age))
+            .header(LAST_MODIFIED, last_modified)
+            .header(CONTENT_TYPE, "application/opensearchdescription+xml; charset=utf-8")
+            .body(Body::from(self.db.opensearch_description()?))?)
+    }
+
+    /// Renders the markdown in the request body as an HTML fragment.
+    async fn preview(&self, req: Request<Body>, client_addr: SocketAddr) -> Result<Response<Body>, ServeError> {
+        let authorized = match &self.preview_token {
+            Some(token) => req.headers().bearer_token() == Some(token.as_str()),
+            None => client_addr.ip().is_loopback(),
+        };
+
+        if !self.preview {
+            return self.not_found(req).await;
+        } else if !authorized {
+            return Ok(Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::empty())?);
+        }
+
+        let markdown = match body::read_limited(req.into_body(), self.max_body_size).await {
+            Ok(markdown) => String::from_utf8(markdown)?,
+            Err(body::Error::TooLarge) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::PAYLOAD_TOO_LARGE)
+                    .body(Body::empty())?);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, "no-store")
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(self.db.render_preview(markdown)?))?)
+    }
+
+    fn web_manifest(&self) -> Result<Response<Body>, ServeError> {
+        let last_modified = self.db.index_updated().to_rfc2822();
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, format!("max-age={}", self.asset_max_age))
+            .header(LAST_MODIFIED, last_modified)
+            .header(CONTENT_TYPE, "application/manifest+json; charset=utf-8")
+            .body(Body::from(self.db.web_manifest()?))?)
+    }
+
+    /// A single sitemap at /sitemap.xml, or once there are more URLs than
+    /// fit in one, pages at /sitemap-{n}.xml listed by /sitemap_index.xml.
+    fn sitemap(&self, req: &Request<Body>, path: &str) -> Result<Response<Body>, ServeError> {
+        if req.headers().conditional(None, &self.db.index_updated(), self.if_modified_since_skew) == Conditional::NotModified {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
+        }
+
+        let urls = self.db.sitemap_urls()?;
+        let pages = urls.chunks(self.sitemap_max_urls).collect_vec();
+
+        let body = if pages.len() <= 1 {
+            match path {
+                "/sitemap.xml" => util::sitemap::urlset(&urls),
+                _ => return Err(ServeError::NotFound),
+            }
+        } else if path == "/sitemap.xml" {
+            return Self::redirect_permanent("/sitemap_index.xml");
+        } else if path == "/sitemap_index.xml" {
+            let site_url = self.db.site_url()?;
+            let page_urls = (1..=pages.len())
+                .map(|n| site_url.join(&format!("sitemap-{n}.xml")).expect("site_url shall be a base"))
+                .collect_vec();
+            util::sitemap::index(&page_urls, &self.db.index_updated().into())
+        } else {
+            let page = path
+                .strip_prefix("/sitemap-")
+                .and_then(|rest| rest.strip_suffix(".xml"))
+                .and_then(|n| n.parse::<usize>().ok())
+                .and_then(|n| pages.get(n.checked_sub(1)?))
+                .ok_or(ServeError::NotFound)?;
+            util::sitemap::urlset(page)
+        };
+
+        let last_modified = self.db.index_updated().to_rfc2822();
+        let cache_control = format!("max-age={}", self.max_age);
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, cache_control)
+            .header(LAST_MODIFIED, last_modified)
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(Body::from(body))?)
+    }
+
+    /// Every post's metadata, for client apps. `?tag=` only includes posts
+    /// with a tag, and `?limit=` caps the number of posts.
+    fn posts_json(&self, req: &Request<Body>) -> Result<Response<Body>, ServeError> {
+        if req.headers().conditional(None, &self.db.index_updated(), self.if_modified_since_skew) == Conditional::NotModified {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
+        }
+
+        let tag = Self::query_param(req, "tag");
+        let limit = match Self::query_param(req, "limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| ServeError::BadRequest(format!("Invalid limit {limit:?}")))?,
+            None => usize::MAX,
+        };
+
+        let body = self.db.posts_json(tag.as_deref(), limit)?;
+        let last_modified = self.db.index_updated().to_rfc2822();
+        let cache_control = format!("max-age={}", self.max_age);
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CACHE_CONTROL, cache_control)
+            .header(LAST_MODIFIED, last_modified)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))?)
+    }
+
+    fn error_response(status: StatusCode, err: ServeError) -> Result<Response<Body>, hyper::http::Error> {
+        Response::builder()
+            .status(status)
+            .body(Body::from(format!("error: {err}")))
+    }
+
+    /// The page for an internal error, which is logged instead of shown,
+    /// since its details may include e.g. paths on the server
+    fn error_page(&self) -> Result<Response<Body>, hyper::http::Error> {
+        let site = || -> Result<ErrorPageSite, IndexNotLoaded> {
+            Ok(ErrorPageSite {
+                site_title: self.db.site_title()?.to_owned(),
+                site_title_short: self.db.site_title_short()?.to_owned(),
+                site_url: self.db.site_url()?.clone(),
+                coffee_link: self.db.coffee_url()?.cloned(),
+                theme: self.theme.clone(),
+                header_labels: self.db.header_labels()?.clone(),
+                styles_href: self.styles_href(),
+                theme_color: self.db.theme_color()?.map(|color| color.to_string()),
+                color_scheme: self.db.color_scheme()?.map(|scheme| scheme.to_string()),
+                opensearch: self.db.opensearch()?,
+            })
+        };
+
+        let vdom = VirtualDom::new_with_props(view::error_page, ErrorPageProps { site: site().ok() });
+        let body = util::render_html(vdom, self.db.lang().unwrap_or_default());
+
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(CACHE_CONTROL, "no-store")
+            .body(Body::from(body))
+    }
+
+    fn redirect_permanent(location: &str) -> Result<Response<Body>, ServeError> {
+        Ok(Response::builder()
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header(LOCATION, location)
+            .body(Body::empty())?)
+    }
+
+    fn robots(&self) -> Result<Response<Body>, ServeError> {
+        let mut robots = ROBOTS_TXT.to_string();
+        for bot in &self.blocked_bots {
+            robots.push_str(&format!("\nUser-agent: {bot}\nDisallow: /\n"));
+        }
+
         Ok(Response::builder()
             .status(StatusCode::OK)
             .header(CONTENT_TYPE, "text/plain; charset=utf-8")
-            .body(Body::from(ROBOTS_TXT))?)
+            .body(Body::from(robots))?)
     }
 
-    async fn not_found(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error>> {
-        let path = req.uri().clone();
-        let method = req.method().clone();
+    async fn not_found(&self, req: Request<Body>) -> Result<Response<Body>, ServeError> {
+        self.not_found_page(req.method().clone(), req.uri())
+    }
+
+    fn not_found_page(&self, method: Method, uri: &Uri) -> Result<Response<Body>, ServeError> {
+        // Only the path, since an absolute request URI could name any host
+        let path = match uri.path_and_query() {
+            Some(path) => Uri::from(path.clone()),
+            None => Uri::from_static("/"),
+        };
+
+        // The 404 page doesn't depend on the index, so it still works if the
+        // index failed to load.
+        let lang = self.db.lang().unwrap_or_default();
 
         let vdom = VirtualDom::new_with_props(view::not_found, NotFoundProps { path, method });
-        let body = util::render_html(vdom, self.db.lang());
+        let body = util::render_html(vdom, lang);
 
         Ok(Response::builder()
             .status(StatusCode::NOT_FOUND)
             .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(CACHE_CONTROL, format!("max-age={}", self.not_found_max_age))
             .body(Body::from(body))?)
     }
 
-    fn is_stupid_bot(req: &Request<Body>) -> bool {
+    /// Languages the client prefers, most preferred first. A `lang` query
+    /// parameter overrides Accept-Language.
+    fn preferred_languages(req: &Request<Body>) -> Vec<String> {
+        let mut languages = req.headers().accepted_languages();
+        if let Some(lang) = Self::query_param(req, "lang") {
+            languages.insert(0, lang.to_lowercase());
+        }
+        languages
+    }
+
+    /// Link to the stylesheet, for every page's `<head>`. A stylesheet in
+    /// the public directory, rather than the theme's, may change while the
+    /// server runs, e.g. with --git-pull-interval, so it is checked again
+    /// at most once per --cache-ttl.
+    fn styles_href(&self) -> String {
+        let mut cached = self.styles_href.lock().unwrap();
+        if self.theme.styles.is_none() && cached.1.elapsed() >= self.cache_ttl {
+            *cached = (styles_href(&self.theme, &self.public_dir), Instant::now());
+        }
+        cached.0.clone()
+    }
+
+    fn query_param<'a>(req: &'a Request<Body>, key: &str) -> Option<Cow<'a, str>> {
+        let query = req.uri().query()?;
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    fn is_stupid_bot(&self, req: &Request<Body>) -> bool {
         req.headers().get_all("User-Agent").iter().any(|h| match h.to_str() {
             Ok(s) => {
                 let s = s.to_lowercase();
-                s.contains("gptbot") || s.contains("claudebot") || s.contains("imagesift")
+                self.blocked_bots.iter().any(|bot| s.contains(&bot.to_lowercase()))
             },
             Err(_) => false
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, fs, net::SocketAddr, ops::Deref, path::{Path, PathBuf}, sync::Arc};
+
+    use clap::Parser;
+    use hyper::{header::{ALLOW, AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, HOST, IF_MODIFIED_SINCE, LAST_MODIFIED, LINK, LOCATION, VARY}, Body, Method, Request, Response, StatusCode, Version};
+    use tokio::sync::RwLock;
+
+    use crate::util::theme::Theme;
+    use super::{Handler, Serve, Server, Sites};
+
+    /// A blog in a temporary directory, which is removed when the test
+    /// ends, even if it fails
+    struct Blog(PathBuf);
+
+    impl Deref for Blog {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for Blog {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Creates a blog with a single post in a temporary directory.
+    fn blog(name: &str) -> Blog {
+        let dir = std::env::temp_dir().join(format!("ustack-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("posts")).unwrap();
+        fs::write(dir.join("index.md"), include_str!("res/default_index.md")).unwrap();
+        fs::write(dir.join("posts/hello.md"), "---\ntitle: Hello\n---\nHello, world!\n").unwrap();
+        Blog(dir)
+    }
+
+    fn sites(dir: &Path, args: &[&str]) -> Arc<Sites> {
+        let dir = dir.to_str().unwrap();
+        let serve = Serve::parse_from([&["serve", "--directory", dir], args].concat());
+        let server = serve.make_server(&serve.directory().unwrap(), &Arc::new(Theme::default())).unwrap();
+        Arc::new(HashMap::from([(None, Arc::new(RwLock::new(server)))]))
+    }
+
+    async fn get(sites: &Arc<Sites>, path: &str) -> Response<Body> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 4198));
+        let req = Request::get(path).body(Body::empty()).unwrap();
+        Server::route(sites.clone(), addr, req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn random_is_not_cached() {
+        let dir = blog("random");
+
+        for mode in ["redirect", "inline"] {
+            let sites = sites(&dir, &["--random-mode", mode]);
+
+            // Load the posts
+            assert_eq!(get(&sites, "/").await.status(), StatusCode::OK);
+
+            let resp = get(&sites, "/random").await;
+            assert!(resp.status() == StatusCode::FOUND || resp.status() == StatusCode::OK);
+            assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "no-store", "{mode}");
+        }
+    }
+
+    #[tokio::test]
+    async fn index_redirects_to_root() {
+        let dir = blog("index");
+        let sites = sites(&dir, &[]);
+
+        for path in ["/index", "/p/index"] {
+            let resp = get(&sites, path).await;
+            assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY, "{path}");
+            assert_eq!(resp.headers().get(LOCATION).unwrap(), "/", "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn links_do_not_leak_bind_port() {
+        let dir = blog("port");
+        fs::write(dir.join("posts/part.md"), "---\ntitle: Part\nseries: Parts\n---\nPart one\n").unwrap();
+
+        for mode in ["redirect", "inline"] {
+            let sites = sites(&dir, &["--random-mode", mode, "--negotiate-language"]);
+
+            // Load the posts
+            assert_eq!(get(&sites, "/").await.status(), StatusCode::OK);
+
+            for path in ["/", "/p/hello", "/archive", "/series/Parts", "/rss", "/random", "/nowhere", "/index"] {
+                // However the request names the server, links come from the site's url
+                let req = Request::get(format!("http://localhost:4198{path}"))
+                    .header(HOST, "localhost:4198")
+                    .body(Body::empty())
+                    .unwrap();
+                let resp = Server::route(sites.clone(), SocketAddr::from(([127, 0, 0, 1], 4198)), req)
+                    .await
+                    .unwrap();
+
+                for (name, value) in resp.headers() {
+                    let value = value.to_str().unwrap();
+                    assert!(!value.contains(":4198"), "{mode} {path}: {name}: {value}");
+                }
+
+                let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                let body = String::from_utf8(body.to_vec()).unwrap();
+                assert!(!body.contains(":4198"), "{mode} {path}: {body}");
+                assert!(!body.contains("localhost"), "{mode} {path}: {body}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sitemap_splits_into_pages() {
+        let dir = blog("sitemap");
+
+        let single = sites(&dir, &[]);
+        assert_eq!(get(&single, "/sitemap.xml").await.status(), StatusCode::OK);
+        assert_eq!(get(&single, "/sitemap_index.xml").await.status(), StatusCode::NOT_FOUND);
+
+        // The homepage and the hello post don't fit on one page
+        let split = sites(&dir, &["--sitemap-max-urls", "1"]);
+        let resp = get(&split, "/sitemap.xml").await;
+        assert_eq!(resp.headers().get(LOCATION).unwrap(), "/sitemap_index.xml");
+
+        let resp = get(&split, "/sitemap_index.xml").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("sitemap-2.xml</loc>"));
+
+        let resp = get(&split, "/sitemap-2.xml").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("/p/hello</loc>"));
+
+        for path in ["/sitemap-0.xml", "/sitemap-3.xml", "/sitemap-x.xml"] {
+            assert_eq!(get(&split, path).await.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn tag_feed_has_only_tagged_posts() {
+        let dir = blog("tag-feed");
+        fs::write(dir.join("posts/tagged.md"), "---\ntitle: Tagged\ntags: [rust lang]\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        let resp = get(&sites, "/tags/rust%20lang/rss").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("— rust lang</title>"));
+        assert!(body.contains("/p/tagged") && !body.contains("/p/hello"));
+
+        assert_eq!(get(&sites, "/tags/nothing/rss").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn tags_over_max_are_only_rejected_when_strict() {
+        let dir = blog("max-tags");
+        let index = include_str!("res/default_index.md").replace("title: My Blog", "title: My Blog\nmax_tags: 2");
+        fs::write(dir.join("index.md"), index).unwrap();
+        fs::write(dir.join("posts/many.md"), "---\ntitle: Many\ntags: [a, b, c]\n---\nHi\n").unwrap();
+        fs::write(dir.join("posts/blank.md"), "---\ntitle: Blank\ntags: [a, ' ']\n---\nHi\n").unwrap();
+
+        let lenient = sites(&dir, &[]);
+        assert_eq!(get(&lenient, "/p/many").await.status(), StatusCode::OK);
+        assert_eq!(get(&lenient, "/p/blank").await.status(), StatusCode::OK);
+
+        let strict = sites(&dir, &["--strict-frontmatter"]);
+        assert_eq!(get(&strict, "/p/hello").await.status(), StatusCode::OK);
+        assert_eq!(get(&strict, "/p/many").await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(get(&strict, "/p/blank").await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn tags_with_slashes_have_feeds() {
+        let dir = blog("slash-tag");
+        fs::write(dir.join("posts/cpp.md"), "---\ntitle: Cpp\ntags: [C/C++]\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        let resp = get(&sites, "/subscribe").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("/tags/C%2FC++/rss"));
+
+        let resp = get(&sites, "/tags/C%2FC++/rss").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("/p/cpp"));
+    }
+
+    #[tokio::test]
+    async fn full_feed_has_every_post() {
+        let dir = blog("full-feed");
+        fs::write(dir.join("posts/second.md"), "---\ntitle: Second\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &["--rss-items", "1"]);
+
+        for (path, items) in [("/rss", 1), ("/rss/all", 2)] {
+            let resp = get(&sites, path).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{path}");
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            assert_eq!(String::from_utf8_lossy(&body).matches("<item>").count(), items, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_json_filters() {
+        let dir = blog("posts-json");
+        fs::write(dir.join("posts/tagged.md"), "---\ntitle: Tagged\ntags: [rust]\n---\nHi\n").unwrap();
+        fs::write(dir.join("posts/wip.md"), "---\ntitle: WIP\ndraft: true\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        let ids = |body: &[u8]| {
+            let posts: serde_json::Value = serde_json::from_slice(body).unwrap();
+            let mut ids = posts.as_array().unwrap()
+                .iter()
+                .map(|post| post["id"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+
+        let resp = get(&sites, "/posts.json").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "application/json");
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(ids(&body), ["hello", "tagged"]);
+        let posts: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(posts[0]["url"].as_str().unwrap().starts_with("https://my-blog.com/p/"));
+
+        let body = hyper::body::to_bytes(get(&sites, "/posts.json?tag=rust").await.into_body()).await.unwrap();
+        assert_eq!(ids(&body), ["tagged"]);
+        let body = hyper::body::to_bytes(get(&sites, "/posts.json?limit=1").await.into_body()).await.unwrap();
+        assert_eq!(ids(&body).len(), 1);
+        assert_eq!(get(&sites, "/posts.json?limit=x").await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn robots_txt_disallows_blocked_bots() {
+        let dir = blog("robots");
+        let sites = sites(&dir, &["--blocked-bots", "GPTBot,CCBot"]);
+
+        let body = hyper::body::to_bytes(get(&sites, "/robots.txt").await.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("User-agent: *\n"));
+        assert!(body.contains("User-agent: CCBot\nDisallow: /\n"));
+        assert!(!body.contains("ClaudeBot"));
+
+        let req = Request::get("/p/hello").header("User-Agent", "Mozilla/5.0 (compatible; ccbot/2.0)").body(Body::empty()).unwrap();
+        let resp = Server::route(sites.clone(), ([127, 0, 0, 1], 4198).into(), req).await.unwrap();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("Hello, world!"));
+    }
+
+    #[tokio::test]
+    async fn http_1_0_connections_close() {
+        let dir = blog("http-1-0");
+        let handler = Handler(sites(&dir, &[]));
+        let addr = SocketAddr::from(([127, 0, 0, 1], 4198));
+
+        let request = |version, keep_alive: bool| {
+            let mut req = Request::get("/robots.txt").version(version);
+            if keep_alive {
+                req = req.header("Connection", "Keep-Alive");
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        let resp = handler.clone().handle(addr, request(Version::HTTP_10, false)).await.unwrap();
+        assert_eq!(resp.headers().get("connection").unwrap(), "close");
+        let resp = handler.clone().handle(addr, request(Version::HTTP_10, true)).await.unwrap();
+        assert!(resp.headers().get("connection").is_none());
+        let resp = handler.handle(addr, request(Version::HTTP_11, false)).await.unwrap();
+        assert!(resp.headers().get("connection").is_none());
+    }
+
+    #[tokio::test]
+    async fn feed_readers_are_sent_to_rss() {
+        let dir = blog("accept-feed");
+        let sites = sites(&dir, &[]);
+
+        let get_accepting = |accept: &'static str| {
+            let req = Request::get("/").header("Accept", accept).body(Body::empty()).unwrap();
+            Server::route(sites.clone(), SocketAddr::from(([127, 0, 0, 1], 4198)), req)
+        };
+
+        let resp = get_accepting("application/rss+xml, */*;q=0.5").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers().get(LOCATION).unwrap(), "/rss");
+
+        let resp = get_accepting("text/html,application/xhtml+xml,*/*;q=0.8").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(VARY).unwrap(), "HX-Request, Accept");
+    }
+
+    #[tokio::test]
+    async fn blog_without_posts() {
+        let dir = blog("no-posts");
+        fs::remove_file(dir.join("posts/hello.md")).unwrap();
+        let sites = sites(&dir, &[]);
+
+        for path in ["/", "/archive"] {
+            let resp = get(&sites, path).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{path}");
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.contains("No posts yet.") && !body.contains("<ol>"), "{path}");
+        }
+
+        let resp = get(&sites, "/random").await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers().get(LOCATION).unwrap(), "/");
+    }
+
+    #[tokio::test]
+    async fn summaries_in_listings() {
+        let dir = blog("summaries");
+        fs::write(dir.join("posts/summed.md"), "---\ntitle: Summed\nsummary: In short\n---\nAt length\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        for path in ["/", "/archive"] {
+            let body = hyper::body::to_bytes(get(&sites, path).await.into_body()).await.unwrap();
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.contains("<p class=\"summary\">In short</p>"), "{path}");
+            assert!(!body.contains("<p class=\"summary\"></p>"), "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn fingerprinted_assets_are_immutable() {
+        let dir = blog("fingerprint");
+        fs::create_dir_all(dir.join("public")).unwrap();
+        let names = ["app.3f9a01c2.js", "app-3f9a01c2.min.js", "app.js", "cafe.png", "IMG_20231225_143012.jpg", "report-20240101.pdf"];
+        for name in names {
+            fs::write(dir.join("public").join(name), "x").unwrap();
+        }
+
+        // Off by default
+        let disabled = sites(&dir, &[]);
+        let resp = get(&disabled, "/public/app.3f9a01c2.js").await;
+        assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "max-age=3600");
+
+        let sites = sites(&dir, &["--asset-max-age", "60", "--fingerprint-len", "8"]);
+        for (name, cache_control) in [
+            ("app.3f9a01c2.js", "public, max-age=31536000, immutable"),
+            ("app-3f9a01c2.min.js", "public, max-age=31536000, immutable"),
+            ("app.js", "max-age=60"),
+            ("cafe.png", "max-age=60"),
+            // Dates, not hashes
+            ("IMG_20231225_143012.jpg", "max-age=60"),
+            ("report-20240101.pdf", "max-age=60"),
+        ] {
+            let resp = get(&sites, &format!("/public/{name}")).await;
+            assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), cache_control, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn opensearch_is_opt_in() {
+        let dir = blog("opensearch");
+        let has_link = |body: &[u8]| String::from_utf8_lossy(body).contains("href=\"/opensearch.xml\"");
+
+        let default = sites(&dir, &[]);
+        let body = hyper::body::to_bytes(get(&default, "/").await.into_body()).await.unwrap();
+        assert!(!has_link(&body));
+        assert_eq!(get(&default, "/opensearch.xml").await.status(), StatusCode::NOT_FOUND);
+
+        let index = include_str!("res/default_index.md").replace("title: My Blog", "title: My Blog\nopensearch: true");
+        fs::write(dir.join("index.md"), index).unwrap();
+        let sites = sites(&dir, &[]);
+        let body = hyper::body::to_bytes(get(&sites, "/").await.into_body()).await.unwrap();
+        assert!(has_link(&body));
+        assert_eq!(get(&sites, "/opensearch.xml").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn archive_pages_under_base_path() {
+        let dir = blog("archive-base-path");
+        let index = include_str!("res/default_index.md").replace("url: https://my-blog.com", "url: https://my-blog.com/blog/");
+        fs::write(dir.join("index.md"), index).unwrap();
+        fs::write(dir.join("posts/apple.md"), "---\ntitle: apple\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &["--archive-page-len", "1"]);
+
+        let resp = get(&sites, "/archive?page=2").await;
+        assert_eq!(resp.headers().get(LINK).unwrap(), "<https://my-blog.com/blog/archive>; rel=\"prev\"");
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(r#"<link rel="canonical" href="https://my-blog.com/blog/archive?page=2"/>"#), "{body}");
+    }
+
+    #[tokio::test]
+    async fn archive_pages_are_conditional() {
+        let dir = blog("archive-conditional");
+        fs::write(dir.join("posts/apple.md"), "---\ntitle: apple\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &["--archive-page-len", "1"]);
+
+        let resp = get(&sites, "/archive?page=2").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let last_modified = resp.headers().get(LAST_MODIFIED).unwrap().clone();
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 4198));
+        for (path, status) in [("/archive?page=2", StatusCode::NOT_MODIFIED), ("/archive?page=3", StatusCode::NOT_FOUND)] {
+            let req = Request::get(path)
+                .header(IF_MODIFIED_SINCE, &last_modified)
+                .body(Body::empty())
+                .unwrap();
+            let resp = Server::route(sites.clone(), addr, req).await.unwrap();
+            assert_eq!(resp.status(), status, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn index_html_redirects() {
+        let dir = blog("index-html");
+        let sites = sites(&dir, &[]);
+
+        for (path, location) in [
+            ("/index.html", "/"),
+            ("/p/hello/index.html", "/p/hello"),
+            ("/archive/index.html?page=2", "/archive?page=2"),
+            ("//evil.com/index.html", "/evil.com"),
+            ("/\\evil.com/index.html", "/evil.com"),
+            ("///index.html", "/"),
+        ] {
+            let resp = get(&sites, path).await;
+            assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY, "{path}");
+            assert_eq!(resp.headers().get(LOCATION).unwrap(), location, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn archive_groups_by_year() {
+        let dir = blog("archive-years");
+        let index = include_str!("res/default_index.md").replace("title: My Blog", "title: My Blog\narchive_group_by: year");
+        fs::write(dir.join("index.md"), index).unwrap();
+        fs::write(dir.join("posts/old.md"), "---\ntitle: old\ncreated: 1 Jan 2020 00:00 +0000\n---\nHi\n").unwrap();
+        fs::write(dir.join("posts/older.md"), "---\ntitle: older\ncreated: 1 Feb 2019 00:00 +0000\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        assert_eq!(get(&sites, "/").await.status(), StatusCode::OK);
+
+        let html = hyper::body::to_bytes(get(&sites, "/archive").await.into_body()).await.unwrap();
+        let html = String::from_utf8_lossy(&html);
+        let years = ["<h2>2020</h2>", "<h2>2019</h2>"].map(|year| html.find(year).unwrap());
+        assert!(years[0] < years[1]);
+
+        let html = hyper::body::to_bytes(get(&sites, "/archive?sort=title").await.into_body()).await.unwrap();
+        assert!(!String::from_utf8_lossy(&html).contains("<h2>2020</h2>"));
+    }
+
+    #[tokio::test]
+    async fn subscribe_lists_feeds() {
+        let dir = blog("subscribe");
+        fs::write(dir.join("posts/tagged.md"), "---\ntitle: tagged\ntags: [rust lang]\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        let resp = get(&sites, "/subscribe").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let html = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let html = String::from_utf8_lossy(&html);
+        for url in ["/rss\"", "/rss/all\"", "/tags/rust%20lang/rss\"", "/posts.json\""] {
+            assert!(html.contains(url), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn other_methods() {
+        let dir = blog("methods");
+        let sites = sites(&dir, &[]);
+        let addr = SocketAddr::from(([127, 0, 0, 1], 4198));
+
+        for (method, path, status) in [
+            (Method::POST, "/p/hello", StatusCode::METHOD_NOT_ALLOWED),
+            (Method::DELETE, "/archive", StatusCode::METHOD_NOT_ALLOWED),
+            (Method::POST, "/wp-login.php", StatusCode::NOT_FOUND),
+            (Method::HEAD, "/p/hello", StatusCode::OK),
+            (Method::HEAD, "/nowhere", StatusCode::NOT_FOUND),
+        ] {
+            let req = Request::builder().method(&method).uri(path).body(Body::empty()).unwrap();
+            let resp = Server::route(sites.clone(), addr, req).await.unwrap();
+            assert_eq!(resp.status(), status, "{method} {path}");
+            if status == StatusCode::METHOD_NOT_ALLOWED {
+                assert_eq!(resp.headers().get(ALLOW).unwrap(), "GET, HEAD");
+            }
+            if method == Method::HEAD {
+                assert!(resp.headers().contains_key(CONTENT_LENGTH), "{path}");
+                assert!(hyper::body::to_bytes(resp.into_body()).await.unwrap().is_empty(), "{path}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn internal_error_page() {
+        let dir = blog("error-page");
+        let sites = sites(&dir, &[]);
+        // Loads the index, for the site's header
+        get(&sites, "/").await;
+        let server = sites.get(&None).unwrap();
+
+        let resp = server.read().await.error_page().unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("500: Internal Server Error"));
+        assert!(body.contains("My Blog") && body.contains("styles.css"));
+    }
+
+    #[tokio::test]
+    async fn rss_waits_for_index() {
+        let dir = blog("rss-index");
+        // Missing the site's url, so the index fails to load
+        fs::write(dir.join("index.md"), "---\ntitle: Blog\n---\n").unwrap();
+        let sites = sites(&dir, &[]);
+
+        let resp = get(&sites, "/rss").await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(!body.contains("<rss") && !body.contains("unspecified.com"));
+
+        fs::write(dir.join("index.md"), include_str!("res/default_index.md")).unwrap();
+        assert_eq!(get(&sites, "/rss").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn archive_sorts_by_title() {
+        let dir = blog("archive-sort");
+        fs::write(dir.join("posts/apple.md"), "---\ntitle: apple\ncreated: 1 January 2020 10:00 +0000\n---\nHi\n").unwrap();
+        let sites = sites(&dir, &["--archive-page-len", "1"]);
+
+        assert_eq!(get(&sites, "/").await.status(), StatusCode::OK);
+
+        let resp = get(&sites, "/archive?sort=title").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("<h3>apple</h3>"));
+        assert!(body.contains("href=\"/archive?sort=title&page=2\""));
+
+        let resp = get(&sites, "/archive").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<h3>Hello</h3>"));
+    }
+
+    #[tokio::test]
+    async fn max_age_is_independent_of_cache_ttl() {
+        let dir = blog("max-age");
+        let sites = sites(&dir, &["--cache-ttl", "5", "--max-age", "900", "--asset-max-age", "60"]);
+
+        for path in ["/", "/p/hello", "/rss"] {
+            let resp = get(&sites, path).await;
+            let cache_control = resp.headers().get(CACHE_CONTROL).unwrap().to_str().unwrap();
+            assert!(cache_control.ends_with("max-age=900"));
+        }
+        assert_eq!(get(&sites, "/public/favicon.png").await.headers().get(CACHE_CONTROL).unwrap(), "max-age=60");
+    }
+
+    #[tokio::test]
+    async fn htmx_requests_get_fragments() {
+        let dir = blog("fragment");
+        let sites = sites(&dir, &[]);
+
+        for path in ["/", "/p/hello", "/archive"] {
+            let req = Request::get(path).header("HX-Request", "true").body(Body::empty()).unwrap();
+            let resp = Server::route(sites.clone(), SocketAddr::from(([127, 0, 0, 1], 4198)), req).await.unwrap();
+            assert!(resp.headers().get(VARY).unwrap().to_str().unwrap().starts_with("HX-Request"), "{path}");
+
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.contains("<header") && !body.contains("<head>") && !body.contains("<main"));
+
+            let body = hyper::body::to_bytes(get(&sites, path).await.into_body()).await.unwrap();
+            assert!(String::from_utf8_lossy(&body).starts_with("<!DOCTYPE html>"));
+        }
+    }
+
+    #[tokio::test]
+    async fn drafts_only_with_show_drafts() {
+        let dir = blog("drafts");
+        fs::write(dir.join("posts/wip.md"), "---\ntitle: Work in Progress\ndraft: true\n---\nSoon\n").unwrap();
+
+        for (args, shown) in [(&[][..], false), (&["--show-drafts"][..], true)] {
+            let sites = sites(&dir, args);
+
+            let resp = get(&sites, "/archive").await;
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            assert_eq!(String::from_utf8_lossy(&body).contains("Work in Progress"), shown);
+
+            let status = get(&sites, "/p/wip").await.status();
+            assert_eq!(status == StatusCode::OK, shown);
+        }
+    }
+
+    #[tokio::test]
+    async fn default_favicon() {
+        let dir = blog("favicon");
+        let sites = sites(&dir, &[]);
+
+        let resp = get(&sites, "/public/favicon.png").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "image/png");
+
+        assert_eq!(get(&sites, "/public/other.png").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn case_insensitive_ids_redirect() {
+        let dir = blog("case");
+        let sites = sites(&dir, &["--case-insensitive-ids"]);
+
+        let resp = get(&sites, "/p/HeLLo?x=1").await;
+        assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(resp.headers().get(LOCATION).unwrap(), "/p/hello?x=1");
+
+        assert_eq!(get(&sites, "/p/hello").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn evicted_posts_are_read_again() {
+        let dir = blog("evict");
+        fs::write(dir.join("posts/other.md"), "---\ntitle: Other\n---\nAnother post\n").unwrap();
+        let sites = sites(&dir, &["--max-cached-posts", "1"]);
+
+        assert_eq!(get(&sites, "/").await.status(), StatusCode::OK);
+        for id in ["hello", "other", "hello"] {
+            assert_eq!(get(&sites, &format!("/p/{id}")).await.status(), StatusCode::OK);
+        }
+
+        let resp = get(&sites, "/p/hello").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("Hello, world!"));
+
+        let resp = get(&sites, "/archive").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("Hello") && body.contains("Other"));
+    }
+
+    #[tokio::test]
+    async fn random_reads_evicted_posts_again() {
+        let dir = blog("random-evict");
+        fs::write(dir.join("posts/other.md"), "---\ntitle: Other\n---\nAnother post\n").unwrap();
+        let sites = sites(&dir, &["--max-cached-posts", "1", "--random-mode", "inline"]);
+
+        assert_eq!(get(&sites, "/").await.status(), StatusCode::OK);
+        for _ in 0..8 {
+            let resp = get(&sites, "/random").await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.contains("Hello, world!") || body.contains("Another post"));
+        }
+    }
+
+    async fn preview(sites: &Arc<Sites>, markdown: &str, token: Option<&str>) -> Response<Body> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 4198));
+        let mut req = Request::post("/preview");
+        if let Some(token) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let req = req.body(Body::from(markdown.to_string())).unwrap();
+        Server::route(sites.clone(), addr, req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn preview_renders_markdown() {
+        let dir = blog("preview");
+
+        let resp = preview(&sites(&dir, &[]), "*hi*", None).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let sites = sites(&dir, &["--preview", "--max-body-size", "32"]);
+        let resp = preview(&sites, "*hi*", None).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, "<p><em>hi</em></p>\n");
+
+        let resp = preview(&sites, &"long ".repeat(10), None).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn preview_requires_token() {
+        let dir = blog("preview-token");
+        let sites = sites(&dir, &["--preview-token", "secret"]);
+
+        assert_eq!(preview(&sites, "hi", None).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(preview(&sites, "hi", Some("wrong")).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(preview(&sites, "hi", Some("secret")).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn canonical_url_drops_tracking_params() {
+        let dir = blog("canonical");
+        let sites = sites(&dir, &["--canonical-query-params", "page"]);
+
+        let resp = get(&sites, "/p/hello?utm_source=x&page=2").await;
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains(r#"<link rel="canonical" href="https://my-blog.com/p/hello?page=2"/>"#), "{body}");
+        assert!(!body.contains("utm_source"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn missing_post_is_not_found() {
+        let dir = blog("missing");
+        let sites = sites(&dir, &[]);
+
+        assert_eq!(get(&sites, "/p/missing").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&sites, "/series/missing").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn not_found_is_cached_briefly() {
+        let dir = blog("not-found");
+
+        let resp = get(&sites(&dir, &[]), "/nowhere").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "max-age=60");
+
+        let resp = get(&sites(&dir, &["--not-found-max-age", "5"]), "/nowhere").await;
+        assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "max-age=5");
+    }
+}
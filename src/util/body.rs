@@ -0,0 +1,52 @@
+//! Size-limited request body reading.
+
+use std::fmt::Display;
+
+use hyper::{body::HttpBody, Body};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The body was larger than the allowed limit.
+    TooLarge,
+    Hyper(hyper::Error),
+}
+
+impl From<hyper::Error> for Error {
+    fn from(value: hyper::Error) -> Self {
+        Self::Hyper(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::TooLarge => write!(f, "request body too large"),
+            Error::Hyper(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Reads the whole body into memory, giving up as soon as it exceeds `limit`
+/// bytes.
+pub async fn read_limited(mut body: Body, limit: usize) -> Result<Vec<u8>, Error> {
+    let exceeds_limit = body
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len > limit as u64);
+    if exceeds_limit {
+        return Err(Error::TooLarge);
+    }
+
+    let mut buffer = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buffer.len() + chunk.len() > limit {
+            return Err(Error::TooLarge);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+
+    Ok(buffer)
+}
@@ -1,17 +1,24 @@
 //! The blog "database" holds the logic for post caching.
 
 use std::{
+    borrow::Cow,
     cell::RefCell,
     cmp::{max, Ordering},
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap},
     error::Error,
+    fmt,
     io::{self, ErrorKind},
-    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
     time::{Duration, SystemTime},
 };
 
-use crate::{model::{IndexMetadata, Metadata}, util};
-use super::mydatetime::MyDateTime;
+use crate::{model::{ArchiveGroupBy, HeaderLabels, IndexLayout, IndexMetadata, MarkdownOptions, Metadata, NavLink, PodcastMetadata}, util::{self, text}};
+use super::{
+    mydatetime::MyDateTime,
+    sitemap::SitemapUrl,
+    source::{ContentSource, MemoryContentSource, INDEX_ID},
+    webhook::Webhook,
+};
 use chrono::{DateTime, FixedOffset, Local};
 use comrak::{
     arena_tree::Node,
@@ -19,32 +26,96 @@ use comrak::{
     Arena, ComrakOptions,
 };
 use itertools::Itertools;
-use log::{debug, error, info, warn};
+use log::{debug, info, warn};
 use rand::{seq::IteratorRandom, thread_rng};
-use rss::{ChannelBuilder, extension::atom::{AtomExtensionBuilder, Link}, ImageBuilder};
-use tokio::{
-    fs::{self, File},
-    io::AsyncReadExt,
+use rss::{
+    ChannelBuilder,
+    extension::{
+        atom::{AtomExtension, AtomExtensionBuilder, Link},
+        itunes::{ITunesCategory, ITunesCategoryBuilder, ITunesChannelExtension, ITunesChannelExtensionBuilder, ITunesOwnerBuilder},
+    },
+    ImageBuilder,
 };
 use url::Url;
 
 pub struct PostDb {
     posts: HashMap<String, PostEntry>,
-    posts_dir: PathBuf,
+    source: Box<dyn ContentSource>,
     ttl: Duration,
     index_updated: SystemTime,
-    index_metadata: IndexMetadata,
-    rss_base: ChannelBuilder
+    /// `None` until the index file has been parsed successfully
+    index_metadata: Option<IndexMetadata>,
+    rss_base: ChannelBuilder,
+    refresh_webhook: Option<Webhook>,
+    parse_options: ParseOptions,
+    /// Whether the posts directory has been searched at least once. Until
+    /// then, posts that are new to the cache are not actually new.
+    searched_all: bool,
+    /// Ids of every post found by the last search, keyed by their lowercase form
+    lowercase_ids: HashMap<String, String>,
+    /// Whether drafts are treated like any other post
+    show_drafts: bool,
+    /// Whether posts with ids like `my-post.fr` are translations
+    translations: bool,
+    /// Most posts whose body is kept in memory
+    max_cached_posts: Option<usize>,
+    /// Counts requests for posts, to find the least recently used
+    clock: AtomicU64,
+}
+
+/// Returned by the site information accessors of [`PostDb`] until the index
+/// file has been parsed successfully.
+#[derive(Debug)]
+pub struct IndexNotLoaded;
+
+impl fmt::Display for IndexNotLoaded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the index file has not been loaded; check that it exists and is valid")
+    }
 }
 
-#[derive(PartialEq, PartialOrd)]
+impl Error for IndexNotLoaded {}
+
 pub struct PostEntry {
     /// The last time the database updated this PostEntry
     updated: SystemTime,
     /// The last time the blog file was modified
     last_modified: SystemTime,
     metadata: Metadata,
-    body: String,
+    /// `None` once evicted by the cache limit, until the post is requested again
+    body: Option<String>,
+    /// The first sentence of the body, kept when the body is evicted so
+    /// that listings don't change with what is cached
+    generated_summary: Option<String>,
+    /// The db's clock when the post was last requested
+    last_used: AtomicU64,
+    /// The markdown the body was rendered from, if the db retains it
+    source: Option<String>,
+}
+
+/// Tags a post may have, unless the index says otherwise
+const DEFAULT_MAX_TAGS: usize = 20;
+
+/// Settings that affect how post files are parsed
+#[derive(Debug, Clone, Copy, Default)]
+struct ParseOptions {
+    /// Reject front matter with unknown keys instead of ignoring them
+    strict_frontmatter: bool,
+    retain_source: bool,
+    /// Strip anything potentially dangerous from rendered HTML
+    sanitize_html: bool,
+    /// Options from the index file
+    markdown: MarkdownOptions,
+}
+
+/// How the RSS feed picks and orders its items
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RssOrder {
+    /// Newest posts first
+    #[default]
+    Published,
+    /// Most recently edited posts first, so edits show up in feed readers
+    Modified,
 }
 
 pub struct Post<'a> {
@@ -58,6 +129,9 @@ pub struct PostMeta {
     pub id: String,
     pub title: String,
     pub summary: Option<String>,
+    pub thumbnail: Option<String>,
+    /// Whether the post was modified within the site's `recently_updated_days`
+    pub recently_updated: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -66,270 +140,666 @@ pub struct PostContent {
     pub body: String,
     pub last_modified: SystemTime,
     pub metadata: Metadata,
+    /// The markdown the body was rendered from, if the db retains it
+    pub source: Option<String>,
 }
 
 impl PostDb {
-    pub fn new(posts_dir: PathBuf, ttl_seconds: u32) -> Result<Self, io::Error> {
-        Ok(Self {
+    pub fn new(source: Box<dyn ContentSource>, ttl_seconds: u32) -> Self {
+        Self {
             posts: HashMap::default(),
-            posts_dir: dunce::canonicalize(posts_dir)?,
+            source,
             ttl: Duration::from_secs(ttl_seconds as u64),
             index_updated: SystemTime::UNIX_EPOCH,
-            index_metadata: IndexMetadata::default(),
-            rss_base: ChannelBuilder::default()
-        })
+            index_metadata: None,
+            rss_base: ChannelBuilder::default(),
+            refresh_webhook: None,
+            parse_options: ParseOptions::default(),
+            searched_all: false,
+            lowercase_ids: HashMap::default(),
+            show_drafts: false,
+            translations: false,
+            max_cached_posts: None,
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    /// A db of the markdown in `posts`, keyed by id with the index under
+    /// `/index`, that never touches the filesystem. Every post is parsed
+    /// up front.
+    pub fn from_memory(posts: HashMap<String, (String, SystemTime)>) -> Result<Self, io::Error> {
+        let ids = posts.keys().filter(|id| *id != INDEX_ID).cloned().collect_vec();
+
+        // Memory doesn't change, so nothing ever needs to be read again
+        let mut db = Self::new(Box::new(MemoryContentSource::new(posts.clone())), u32::MAX);
+
+        // The index decides how every post is rendered
+        if let Some((markdown, modified)) = posts.get(INDEX_ID) {
+            db.parse_index(markdown.clone(), *modified)?;
+        }
+        db.lowercase_ids = lowercase_ids(ids);
+        for id in db.lowercase_ids.values().cloned().collect_vec() {
+            let (markdown, modified) = &posts[&id];
+            db.parse_page(markdown.clone(), *modified, &id)?;
+        }
+
+        db.searched_all = true;
+        db.index_updated = SystemTime::now();
+
+        Ok(db)
+    }
+
+    /// Keep the bodies of at most `max` posts in memory, dropping the least
+    /// recently used. The metadata of every post stays cached, since
+    /// listings need it.
+    pub fn set_max_cached_posts(&mut self, max: usize) {
+        self.max_cached_posts = Some(max);
+    }
+
+    /// Include drafts in listings and feeds, and allow them to be viewed
+    pub fn set_show_drafts(&mut self, show: bool) {
+        self.show_drafts = show;
+    }
+
+    pub fn show_drafts(&self) -> bool {
+        self.show_drafts
+    }
+
+    /// Treat posts with ids like `my-post.fr` as translations of other
+    /// posts, which are left out of listings and feeds. Otherwise they are
+    /// posts like any other.
+    pub fn set_translations(&mut self, enabled: bool) {
+        self.translations = enabled;
+    }
+
+    fn is_translation(&self, id: &str) -> bool {
+        self.translations && is_translation(id)
+    }
+
+    /// Notify `url` whenever a post is re-read from disk
+    pub fn set_refresh_webhook(&mut self, url: Url) {
+        self.refresh_webhook = Some(Webhook::new(url));
+    }
+
+    /// Reject posts whose front matter has keys that ustack doesn't know
+    pub fn set_strict_frontmatter(&mut self, strict: bool) {
+        self.parse_options.strict_frontmatter = strict;
+    }
+
+    /// Sanitize the HTML rendered from markdown, for blogs that publish
+    /// posts from untrusted authors
+    pub fn set_sanitize_html(&mut self, sanitize: bool) {
+        self.parse_options.sanitize_html = sanitize;
+    }
+
+    /// Keep the markdown source of posts in memory, alongside the rendered HTML
+    pub fn set_retain_source(&mut self, retain: bool) {
+        self.parse_options.retain_source = retain;
     }
 
     pub fn get<'a>(&'a self, id: &'a str) -> Option<Post<'a>> {
-        self.posts.get(id).map(|entry| Post { id, entry, db: self })
+        let entry = self.posts.get(id)?;
+        entry.last_used.store(self.tick(), AtomicOrdering::Relaxed);
+        Some(Post { id, entry, db: self })
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, AtomicOrdering::Relaxed) + 1
     }
 
-    pub fn get_random_id<'a>(&'a self) -> Option<&'a str> {
+    /// Drops the bodies of the least recently used posts, beyond the limit
+    fn evict(&mut self) {
+        let Some(max) = self.max_cached_posts else {
+            return;
+        };
+
+        let mut cached = self.posts
+            .iter_mut()
+            .filter(|(id, entry)| id.as_str() != "/index" && entry.body.is_some())
+            .map(|(_, entry)| entry)
+            .collect_vec();
+        if cached.len() <= max {
+            return;
+        }
+
+        cached.sort_by_key(|entry| entry.last_used.load(AtomicOrdering::Relaxed));
+        let excess = cached.len() - max;
+        for entry in cached.into_iter().take(excess) {
+            entry.body = None;
+            entry.source = None;
+        }
+        debug!("Evicted {excess} posts from the cache");
+    }
+
+    pub fn get_random_id(&self) -> Option<&str> {
         let mut rng = thread_rng();
-        let choices = self.posts.keys()
-            .filter(|id| !id.starts_with('/'))
-            .choose(&mut rng);
-        choices.map(|id| id.as_str())
+        self.content_posts()
+            .choose(&mut rng)
+            .map(|post| post.id)
     }
 
-    pub fn all_posts<'a>(&'a self) -> impl Iterator<Item = Post<'a>> {
+    /// Posts that belong in listings and feeds. Every listing should start
+    /// from here, so that special entries like the index, and translations
+    /// of other posts, never leak into them.
+    pub fn content_posts(&self) -> impl Iterator<Item = Post<'_>> {
         self.posts
             .iter()
-            .filter(|(id, _)| !id.starts_with("/"))
+            .filter(|(id, _)| !id.starts_with('/') && !self.is_translation(id))
+            .filter(|(_, entry)| self.show_drafts || !entry.metadata.draft)
             .map(|(id, entry)| Post { id, entry, db: self })
     }
 
+    /// Every tag that a post has, in alphabetical order
+    pub fn tags(&self) -> Vec<&str> {
+        self.content_posts()
+            .flat_map(|post| post.metadata().tags.iter().map(|tag| tag.as_str()))
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    /// Whether any post has `tag`
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.content_posts().any(|post| post.has_tag(tag))
+    }
+
+    /// Pinned posts, sorted by weight and then by recency
+    pub fn pinned_posts<'a>(&'a self) -> impl Iterator<Item = Post<'a>> {
+        self.content_posts()
+            .filter(|post| post.metadata().pinned)
+            .sorted_by(|a, b| {
+                b.metadata().weight.cmp(&a.metadata().weight)
+                    .then_with(|| b.cmp_published(a))
+            })
+    }
+
+    /// Posts in a series, in reading order
+    pub fn series_members<'a>(&'a self, series: &'a str) -> impl Iterator<Item = Post<'a>> {
+        self.content_posts()
+            .filter(move |post| post.metadata().series.as_deref() == Some(series))
+            .sorted_by(|a, b| {
+                let a_order = a.metadata().series_order.unwrap_or(u32::MAX);
+                let b_order = b.metadata().series_order.unwrap_or(u32::MAX);
+                a_order.cmp(&b_order)
+                    .then_with(|| a.cmp_published(b))
+            })
+    }
+
+    /// The newest post other than `id` or its translations, to suggest
+    /// reading next
+    pub fn newest_excluding(&self, id: &str) -> Option<PostMeta> {
+        let id = if self.translations { untranslated_id(id) } else { id };
+        self.content_posts()
+            .filter(|post| post.id() != id)
+            .max_by(|a, b| a.cmp_published(b))
+            .map(|post| post.to_post_meta())
+    }
+
+    /// The number of published posts. Drafts aren't counted, even with
+    /// `show_drafts`.
+    pub fn post_count(&self) -> usize {
+        self.content_posts()
+            .filter(|post| !post.metadata().draft)
+            .count()
+    }
+
+    /// When the newest published post was published
+    pub fn latest_post_date(&self) -> Option<DateTime<FixedOffset>> {
+        self.content_posts()
+            .filter(|post| !post.metadata().draft)
+            .max_by(|a, b| a.cmp_published(b))
+            .map(|post| post.published())
+    }
+
+    /// Renders markdown the same way posts are rendered, without saving it
+    /// or touching the cache.
+    pub fn render_preview(&self, markdown: String) -> Result<String, io::Error> {
+        let parser = Parser::new(markdown, self.parse_options.markdown);
+        let root = parser.parse()?;
+        parser.generate_html(root, self.parse_options.sanitize_html)
+    }
+
+    /// The id of the post whose id matches `id` ignoring case, as of the
+    /// last search of the posts directory.
+    pub fn canonical_id(&self, id: &str) -> Option<&str> {
+        self.lowercase_ids.get(&id.to_lowercase()).map(|id| id.as_str())
+    }
+
+    /// Languages of the cached translations of a post
+    pub fn translations<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a str> {
+        self.posts
+            .keys()
+            .filter_map(move |key| key.strip_prefix(id)?.strip_prefix('.'))
+            .filter(|lang| is_language_tag(lang))
+            .sorted()
+    }
+
     /// The last time any file in the db was modified
     pub fn index_updated(&self) -> DateTime<Local> {
         self.index_updated.into()
     }
 
+    /// Site information from the index file
+    pub fn site(&self) -> Result<&IndexMetadata, IndexNotLoaded> {
+        self.index_metadata.as_ref().ok_or(IndexNotLoaded)
+    }
+
     /// Generates a twitter sharing link
     pub fn twitter_link(&self, id: &str) -> Result<Option<Url>, Box<dyn Error>> {
-        self.index_metadata.twitter_link(id)
+        self.site()?.twitter_link(id)
+    }
+
+    /// Where the post with `id` can be edited, if the site has an edit URL
+    pub fn edit_link(&self, id: &str) -> Result<Option<Url>, IndexNotLoaded> {
+        Ok(self.site()?.edit_link(id))
+    }
+
+    /// Generates the OpenSearch description document
+    pub fn opensearch_description(&self) -> Result<String, IndexNotLoaded> {
+        Ok(self.site()?.opensearch_description())
+    }
+
+    /// Whether pages link to the OpenSearch description document
+    pub fn opensearch(&self) -> Result<bool, IndexNotLoaded> {
+        Ok(self.site()?.opensearch)
+    }
+
+    /// Generates the web app manifest
+    pub fn web_manifest(&self) -> Result<String, IndexNotLoaded> {
+        Ok(self.site()?.web_manifest())
+    }
+
+    /// Color of the browser UI, if the site sets one
+    pub fn theme_color(&self) -> Result<Option<&str>, IndexNotLoaded> {
+        Ok(self.site()?.theme_color.as_deref())
+    }
+
+    /// Color schemes the site's styles support, e.g. `light dark`
+    pub fn color_scheme(&self) -> Result<Option<&str>, IndexNotLoaded> {
+        Ok(self.site()?.color_scheme.as_deref())
     }
 
     /// Blog title
-    pub fn site_title(&self) -> &str {
-        &self.index_metadata.title
+    pub fn site_title(&self) -> Result<&str, IndexNotLoaded> {
+        Ok(&self.site()?.title)
     }
 
     /// Blog title
-    pub fn site_title_short(&self) -> &str {
-        let site_title = self.site_title();
-        self.index_metadata.short_title
+    pub fn site_title_short(&self) -> Result<&str, IndexNotLoaded> {
+        let site = self.site()?;
+        Ok(site.short_title
             .as_deref()
-            .unwrap_or(site_title)
+            .unwrap_or(&site.title))
     }
 
     /// Blog URL
-    pub fn site_url(&self) -> &Url {
-        &self.index_metadata.url
+    pub fn site_url(&self) -> Result<&Url, IndexNotLoaded> {
+        Ok(&self.site()?.url)
     }
 
     /// Coffee URL
-    pub fn coffee_url(&self) -> Option<&Url> {
-        self.index_metadata.coffee.as_ref()
+    pub fn coffee_url(&self) -> Result<Option<&Url>, IndexNotLoaded> {
+        Ok(self.site()?.coffee.as_ref())
     }
 
-    pub fn ttl(&self) -> Duration {
-        self.ttl
+    /// Comments script, relative to the public directory
+    pub fn comments_script(&self) -> Result<Option<&str>, IndexNotLoaded> {
+        Ok(self.site()?.comments.as_deref())
     }
 
     /// Post URL
-    pub fn post_url(&self, post: &Post<'_>) -> Url {
-        let mut result = self.site_url().clone();
+    pub fn post_url(&self, post: &Post<'_>) -> Result<Url, IndexNotLoaded> {
+        let mut result = self.site_url()?.clone();
         result.path_segments_mut()
             .expect("site_url shall be a base")
+            .pop_if_empty()
             .extend(&["p", post.id()]);
-        result
+        Ok(result)
     }
 
-    pub fn site_summary(&self) -> Option<&str> {
-        self.index_metadata.summary.as_deref()
+    /// Every post as JSON, newest first, for client apps. With `tag`, only
+    /// posts with that tag are included.
+    pub fn posts_json(&self, tag: Option<&str>, limit: usize) -> Result<String, IndexNotLoaded> {
+        let posts = self.content_posts()
+            .filter(|post| tag.is_none_or(|tag| post.has_tag(tag)))
+            .sorted_by(|a, b| b.cmp_published(a))
+            .take(limit)
+            .map(|post| {
+                let meta = post.to_post_meta();
+                Ok(serde_json::json!({
+                    "id": meta.id,
+                    "title": meta.title,
+                    "summary": meta.summary,
+                    "tags": post.metadata().tags,
+                    "published": post.published().to_rfc3339(),
+                    "url": self.post_url(&post)?.as_str(),
+                }))
+            })
+            .collect::<Result<Vec<_>, IndexNotLoaded>>()?;
+
+        Ok(serde_json::Value::from(posts).to_string())
     }
 
-    pub fn lang(&self) -> &str {
-        &self.index_metadata.lang
+    /// The homepage and every post, oldest first, so that pages of a split
+    /// sitemap only change at the end as posts are added
+    pub fn sitemap_urls(&self) -> Result<Vec<SitemapUrl>, IndexNotLoaded> {
+        let home = SitemapUrl {
+            loc: self.site_url()?.clone(),
+            lastmod: self.posts
+                .get("/index")
+                .map_or(self.index_updated, |index| index.last_modified)
+                .into(),
+        };
+
+        let posts = self.content_posts()
+            .sorted_by(|a, b| a.cmp_published(b))
+            .map(|post| Ok(SitemapUrl {
+                loc: self.post_url(&post)?,
+                lastmod: post.last_modified(),
+            }));
+
+        std::iter::once(Ok(home)).chain(posts).collect()
+    }
+
+    pub fn lang(&self) -> Result<&str, IndexNotLoaded> {
+        Ok(&self.site()?.lang)
+    }
+
+    pub fn index_layout(&self) -> Result<IndexLayout, IndexNotLoaded> {
+        Ok(self.site()?.index_layout)
+    }
+
+    pub fn archive_group_by(&self) -> Result<ArchiveGroupBy, IndexNotLoaded> {
+        Ok(self.site()?.archive_group_by)
+    }
+
+    /// Whether posts' footers show the number of posts and the newest's date
+    pub fn footer_stats(&self) -> Result<bool, IndexNotLoaded> {
+        Ok(self.site()?.footer_stats)
     }
-    
+
+    /// How recently a post must have been updated to be marked as recently
+    /// updated, if posts are marked at all
+    pub fn recently_updated_window(&self) -> Option<Duration> {
+        let days = self.site().ok()?.recently_updated_days?;
+        Some(Duration::from_secs(u64::from(days) * 24 * 60 * 60))
+    }
+
+    /// Whether a post without an `updated` date counts as updated when its
+    /// file was last modified
+    pub fn recently_updated_from_mtime(&self) -> bool {
+        self.site().is_ok_and(|site| site.recently_updated_from_mtime)
+    }
+
+    /// Names of the header's icon links, for screen readers
+    pub fn header_labels(&self) -> Result<&HeaderLabels, IndexNotLoaded> {
+        Ok(&self.site()?.header_labels)
+    }
+
+    /// Links below the index's header
+    pub fn nav_links(&self) -> Result<Vec<NavLink>, IndexNotLoaded> {
+        Ok(self.site()?.nav_links())
+    }
+
     pub async fn refresh_index<'a>(
         &'a mut self,
         allow_search_all: bool,
     ) -> Result<Post<'a>, io::Error> {
         if allow_search_all && self.index_updated + self.ttl <= SystemTime::now() {
-            let mut posts_dir_iter = fs::read_dir(&self.posts_dir).await?;
-            while let Some(ent) = posts_dir_iter.next_entry().await? {
-                let path = PathBuf::from(ent.file_name());
-
-                let is_markdown = path.extension().map_or(false, |ext| ext == "md");
-                let is_dotted = path
-                    .file_name()
-                    .map_or(false, |name| name.to_string_lossy().starts_with('.'));
-
-                if is_dotted || !is_markdown {
-                    continue;
-                }
-
-                if let Some(id) = path.with_extension("").file_name().and_then(|s| s.to_str()) {
-                    debug!("refreshing");
-                    if !self.posts.contains_key(id) {
-                        self.refresh(id).await?;
-                    }
-                } else {
-                    debug!("not valid");
+            let ids = self.source.list().await?;
+            self.lowercase_ids = lowercase_ids(ids.clone());
+            // Posts that only differ in case from another post are left out,
+            // like they would be on a case-insensitive filesystem
+            self.posts.retain(|id, _| id == INDEX_ID || !is_shadowed(&self.lowercase_ids, id));
+            for id in &ids {
+                debug!("refreshing");
+                if !self.posts.contains_key(id) && !is_shadowed(&self.lowercase_ids, id) {
+                    self.refresh(id).await?;
                 }
             }
             self.index_updated = SystemTime::now();
+            self.searched_all = true;
         }
 
-        let post_file = dunce::canonicalize(self.posts_dir.join("../index.md"))?;
-        self.refresh_inner("/index", post_file).await
+        self.refresh_inner(INDEX_ID).await
     }
 
-    pub fn get_rss(&self, since: Option<&DateTime<FixedOffset>>, include_content: bool, max: usize) -> ChannelBuilder
+    pub async fn get_rss(
+        &self,
+        since: Option<&DateTime<FixedOffset>>,
+        include_content: bool,
+        summary_len: Option<usize>,
+        max: usize,
+        order: RssOrder,
+        tag: Option<&str>,
+    ) -> Result<ChannelBuilder, IndexNotLoaded>
     {
+        // rss_base is only filled in once the index has loaded
+        let site = self.site()?;
         let mut builder = self.rss_base.clone();
 
-        let items = self.all_posts()
-            .filter(|p| p.metadata().created.as_deref() >= since)
-            .sorted_by(|a, b| b.cmp_published(a))
-            .take(max)
-            .map(|p| p.to_rss_item(include_content))
-            .collect_vec();
+        if let Some(tag) = tag {
+            builder.title(quick_xml::escape::partial_escape(&format!("{} — {tag}", site.title)));
+
+            let mut url = site.url.clone();
+            url.path_segments_mut()
+                .expect("site_url shall be a base")
+                .pop_if_empty()
+                .extend(&["tags", tag, "rss"]);
+            builder.atom_ext(Some(atom_self_link(url)));
+        }
+
+        let posts = self.content_posts()
+            .filter(|p| tag.is_none_or(|tag| p.has_tag(tag)));
+
+        let posts = match order {
+            RssOrder::Published => posts
+                .filter(|p| p.metadata().created.as_deref() >= since)
+                .sorted_by(|a, b| b.cmp_published(a))
+                .collect_vec(),
+            RssOrder::Modified => posts
+                .filter(|p| since.is_none_or(|since| *p.last_modified() >= *since))
+                .sorted_by(|a, b| b.entry.last_modified.cmp(&a.entry.last_modified))
+                .collect_vec(),
+        };
+
+        let mut items = Vec::new();
+        for p in posts.into_iter().take(max) {
+            // Evicted posts are rendered again, without being cached, so
+            // that the feed doesn't change with what is cached
+            let evicted_body = match p.body() {
+                None if include_content => self.render_uncached(p.id()).await,
+                _ => None,
+            };
+            let content = p.body().or(evicted_body.as_deref()).filter(|_| include_content);
+
+            let mut item = p.to_rss_item(content, summary_len)?;
+            if order == RssOrder::Modified {
+                item.set_pub_date(p.last_modified().to_string_rss());
+            }
+            items.push(item);
+        }
 
         builder.items(items);
 
-        builder
+        Ok(builder)
     }
 
-    fn validate_post_path(&self, id: &str, path: &Path) -> Result<(), io::Error> {
-        fn invalid_path(id: &str) -> io::Error {
-            io::Error::new(
-                ErrorKind::InvalidData,
-                format!("Invalid post id {id:?}")
-            )
-        }
+    /// Reads and renders the body of the post with `id`, without caching it
+    async fn render_uncached(&self, id: &str) -> Option<String> {
+        let (contents, modified) = self.source.read(id).await
+            .inspect_err(|err| warn!("Reading evicted post {id}: {err}"))
+            .ok()?;
+        let markdown = String::from_utf8(contents).ok()?;
+        PostEntry::parse(markdown, modified, self.parse_options)
+            .inspect_err(|err| warn!("Rendering evicted post {id}: {err}"))
+            .ok()?
+            .body
+    }
 
-        let valid_filename =
-            path.extension().map_or(false, |ext| ext == "md") &&
-            path.file_name().map_or(false, |name| {
-                name.to_str().map_or(false, |name| name.starts_with(|c| matches!(c, 'A'..='Z' | 'a'..='z' | '0'..='9')))
-            }
-        );
+    /// Picks the variant of a post that best matches `languages`, which are
+    /// in order of preference. Translations are stored as `{id}.{lang}.md`,
+    /// next to `{id}.md` which is in the site's language.
+    ///
+    /// Returns the id of the chosen variant and its language.
+    pub async fn resolve_variant(&self, id: &str, languages: &[String]) -> (String, String) {
+        let site_lang = self.lang().unwrap_or_default();
+        let site_lang_lower = site_lang.to_lowercase();
+        let site_primary = primary_language(&site_lang_lower);
+
+        for lang in languages {
+            let candidates = [lang.as_str(), primary_language(lang)];
+
+            for candidate in candidates.into_iter().dedup() {
+                if candidate == site_lang_lower || candidate == site_primary {
+                    return (id.to_string(), site_lang.to_string());
+                }
 
-        let valid_parent_path = path.starts_with(&self.posts_dir);
+                let is_valid = !candidate.is_empty()
+                    && candidate.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+                if !is_valid {
+                    continue;
+                }
 
-        if !valid_parent_path {
-            warn!("Suspicious post id={id:?} did not start with canonical posts_dir");
-            Err(invalid_path(id))
-        } else if !valid_filename {
-            Err(invalid_path(id))
-        } else {
-            Ok(())
+                let variant = format!("{id}.{candidate}");
+                if self.source.exists(&variant).await.unwrap_or(false) {
+                    return (variant, candidate.to_string());
+                }
+            }
         }
-    }
 
-    fn get_unvalidated_post_path(&self, id: &str) -> Result<PathBuf, io::Error> {
-        dunce::canonicalize(self.posts_dir.join(id).with_extension("md"))
+        (id.to_string(), site_lang.to_string())
     }
 
     /// Refresh db entry for a particular post
     pub async fn refresh<'a>(&'a mut self, id: &'a str) -> Result<Post<'a>, io::Error> {
-        let post_file = match self.get_unvalidated_post_path(id) {
-            Ok(path) => {
-                self.validate_post_path(id, &path)?;
-                path
-            }
-            Err(err) => {
-                if err.kind() == ErrorKind::NotFound {
-                    debug!("No such post with id {id}, trying to delete it from cache");
-                    self.posts.remove(id);
-                    return Err(err);
-                } else {
-                    info!("Refresh request for id={id:?} caused error: {err}");
-                    return Err(io::Error::new(
-                        ErrorKind::InvalidData,
-                        format!("Invalid post id {id:?}"),
-                    ));
-                }
-            }
-        };
-
-        self.refresh_inner(id, post_file).await
+        self.refresh_inner(id).await
     }
 
-    async fn refresh_inner<'a>(
-        &'a mut self,
-        id: &'a str,
-        post_file: PathBuf,
-    ) -> Result<Post<'a>, io::Error> {
-        let updated = self.posts.get(id).map(|ent| ent.updated);
+    async fn refresh_inner<'a>(&'a mut self, id: &'a str) -> Result<Post<'a>, io::Error> {
+        // An evicted post has to be read again
+        let updated = self.posts.get(id)
+            .filter(|ent| ent.body.is_some())
+            .map(|ent| ent.updated);
 
         if updated.map_or(false, |updated| updated + self.ttl >= SystemTime::now()) {
             // file is not due for another check yet
             return Ok(self.get(id).unwrap());
         }
 
-        let file = File::open(&post_file).await.map_err(|err| {
+        if id != INDEX_ID && is_shadowed(&self.lowercase_ids, id) {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("Post id {id:?} is shadowed by one that differs only in case"),
+            ));
+        }
+
+        let forget_missing = |posts: &mut HashMap<String, PostEntry>, err: &io::Error| {
             if err.kind() == ErrorKind::NotFound {
                 debug!("No such post with id {id}, trying to delete it from cache");
-                self.posts.remove(id);
-                err
-            } else {
-                error!("{err} (opening {post_file:?})");
-                err
+                posts.remove(id);
             }
-        })?;
+        };
 
-        let file_modified_time = file.metadata().await?.modified()?;
+        if let Some(updated) = updated {
+            let file_modified_time = self.source.modified(id).await
+                .inspect_err(|err| forget_missing(&mut self.posts, err))?;
 
-        if updated.map_or(false, |updated| updated >= file_modified_time) {
-            // file has not been changed since last check
-            self.posts.get_mut(id).unwrap().updated = SystemTime::now();
-            return Ok(self.get(id).unwrap());
+            if updated >= file_modified_time {
+                // file has not been changed since last check
+                self.posts.get_mut(id).unwrap().updated = SystemTime::now();
+                return Ok(self.get(id).unwrap());
+            }
         }
 
-        if id == "/index" {
-            self.parse_index(file).await?;
+        let (contents, file_modified_time) = self.source.read(id).await
+            .inspect_err(|err| forget_missing(&mut self.posts, err))?;
+
+        let markdown = String::from_utf8(contents)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+        if id == INDEX_ID {
+            self.parse_index(markdown, file_modified_time)?;
         } else {
-            self.parse_page(file, id).await?;
+            self.parse_page(markdown, file_modified_time, id)?;
         }
 
         Ok(self.get(id).unwrap())
     }
 
-    async fn parse_index(&mut self, file: File) -> Result<(), io::Error> {
-        let (entry, meta) = PostEntry::parse_index(file).await?;
+    fn parse_index(&mut self, markdown: String, last_modified: SystemTime) -> Result<(), io::Error> {
+        let (entry, meta) = PostEntry::parse_index(markdown, last_modified, self.parse_options)
+            .inspect_err(|err| warn!("Failed to parse /index: {err}"))?;
+        let is_change = self.posts.contains_key("/index");
+
+        if meta.markdown != self.parse_options.markdown {
+            // Render every post again with the new options, the next time
+            // it is requested
+            self.parse_options.markdown = meta.markdown;
+            for entry in self.posts.values_mut() {
+                entry.updated = SystemTime::UNIX_EPOCH;
+            }
+        }
 
         self.index_updated = max(entry.last_modified, self.index_updated);
-        self.index_metadata = meta;
+        self.rss_base = self.make_rss_base(&meta);
+        self.index_metadata = Some(meta);
         self.posts.insert("/index".to_string(), entry);
-        self.rss_base = self.make_rss_base();
 
         info!("Refreshed /index and RSS");
 
+        if let Some(webhook) = self.refresh_webhook.as_ref().filter(|_| is_change) {
+            webhook.notify("/index", self.index_updated);
+        }
+
         Ok(())
     }
 
-    async fn parse_page(&mut self, file: File, id: &str) -> Result<(), io::Error> {
-        let entry = PostEntry::parse(file).await?;
+    fn parse_page(&mut self, markdown: String, last_modified: SystemTime, id: &str) -> Result<(), io::Error> {
+        let entry = PostEntry::parse(markdown, last_modified, self.parse_options)
+            .inspect_err(|err| warn!("Failed to parse {id}: {err}"))?;
+        let is_change = self.posts.get(id)
+            .map_or(self.searched_all, |old| old.last_modified != last_modified);
 
+        let max_tags = self.index_metadata.as_ref().and_then(|site| site.max_tags).unwrap_or(DEFAULT_MAX_TAGS);
+        let mut problems = entry.metadata.tags.iter()
+            .filter_map(|tag| tag_problem(tag).map(|problem| format!("{id} has tag {tag:?}, which {problem}")))
+            .collect_vec();
+        if entry.metadata.tags.len() > max_tags {
+            problems.push(format!("{id} has {} tags, more than max_tags={max_tags}", entry.metadata.tags.len()));
+        }
+        for message in &problems {
+            warn!("{message}");
+        }
+        if let Some(message) = problems.into_iter().next().filter(|_| self.parse_options.strict_frontmatter) {
+            return Err(io::Error::new(ErrorKind::InvalidData, message));
+        }
+
+        if entry.metadata.created.is_none() && !self.is_translation(id) {
+            warn!("{id} has no created date, so it is sorted by when its file was last modified");
+        }
+
+        entry.last_used.store(self.tick(), AtomicOrdering::Relaxed);
         self.posts.insert(id.to_string(), entry);
+        self.evict();
 
         info!("Refreshed {id}");
 
+        if let Some(webhook) = self.refresh_webhook.as_ref().filter(|_| is_change) {
+            webhook.notify(id, last_modified);
+        }
+
         Ok(())
     }
 
-    fn make_rss_base(&self) -> ChannelBuilder
+    fn make_rss_base(&self, site: &IndexMetadata) -> ChannelBuilder
     {
         use quick_xml::escape::partial_escape;
 
         let mut channel = rss::ChannelBuilder::default();
-        channel.title(partial_escape(self.site_title()));
-        channel.link(partial_escape(self.site_url().as_str()));
-        channel.language(Some(partial_escape(self.lang()).to_string()));
+        channel.title(partial_escape(&site.title));
+        channel.link(partial_escape(site.url.as_str()));
+        channel.language(Some(partial_escape(&site.lang).to_string()));
         channel.last_build_date(Some(MyDateTime::from(self.index_updated).to_string_rss()));
 
         let ttl_as_minutes = (self.ttl.as_secs() + 59) / 60;
@@ -340,39 +810,169 @@ impl PostDb {
 
         channel.image(Some(ImageBuilder::default()
             .url({
-                let mut url = self.site_url().clone();
+                let mut url = site.url.clone();
                 url.path_segments_mut().unwrap()
+                    .pop_if_empty()
                     .extend(&["public", "favicon.png"]);
                 url.to_string()
             })
-            .title(self.site_title().to_string())
-            .link(self.site_url().to_string())
+            .title(site.title.to_string())
+            .link(site.url.to_string())
             .build()
         ));
 
-        let atom = AtomExtensionBuilder::default()
-            .links(vec![
-                {
-                    let mut rss_path = self.site_url().clone();
-                    rss_path.path_segments_mut().unwrap().extend(&["rss"]);
-                    let mut link = Link::default();
-                    link.set_href(rss_path);
-                    link.set_rel("self");
-                    link.set_mime_type(Some("application/rss+xml".to_string()));
-                    link
-                }
-            ])
-            .build();
-        channel.atom_ext(Some(atom));
+        let mut rss_path = site.url.clone();
+        rss_path.path_segments_mut().unwrap().pop_if_empty().push("rss");
+        channel.atom_ext(Some(atom_self_link(rss_path)));
+
+        // Some validators reject a channel without a description
+        let description = site.rss_description.as_deref()
+            .or(site.summary.as_deref())
+            .unwrap_or(&site.title);
+        channel.description(description.to_string());
 
-        if let Some(summary) = self.site_summary() {
-            channel.description(summary.to_string());
+        if let Some(podcast) = &site.podcast {
+            channel.itunes_ext(Some(itunes_channel_ext(site, podcast)));
         }
 
         channel
     }
 }
 
+fn itunes_channel_ext(site: &IndexMetadata, podcast: &PodcastMetadata) -> ITunesChannelExtension {
+    let categories = podcast.categories
+        .iter()
+        .map(|category| {
+            let (name, subcategory) = match category.split_once('/') {
+                Some((name, subcategory)) => (name, Some(subcategory)),
+                None => (category.as_str(), None),
+            };
+            let subcategory = subcategory.map(|sub| {
+                Box::new(ITunesCategoryBuilder::default().text(sub.trim()).build())
+            });
+            ITunesCategoryBuilder::default()
+                .text(name.trim())
+                .subcategory(subcategory)
+                .build()
+        })
+        .collect::<Vec<ITunesCategory>>();
+
+    let image = podcast.image.as_deref().map(|path| {
+        let mut url = site.url.clone();
+        url.path_segments_mut()
+            .expect("site_url shall be a base")
+            .pop_if_empty()
+            .push("public")
+            .extend(path.split('/'));
+        url.to_string()
+    });
+
+    let owner = podcast.owner.as_ref().map(|owner| {
+        ITunesOwnerBuilder::default()
+            .name(owner.name.clone())
+            .email(owner.email.clone())
+            .build()
+    });
+
+    ITunesChannelExtensionBuilder::default()
+        .author(podcast.author.clone().or_else(|| site.author.clone()))
+        .categories(categories)
+        .image(image)
+        .explicit(Some(podcast.explicit.to_string()))
+        .owner(owner)
+        .build()
+}
+
+/// Maps the lowercase form of each of `ids` to the id itself. Of ids that
+/// differ only in case, which can't coexist on a case-insensitive
+/// filesystem, the one that is already lowercase wins, and otherwise the
+/// first in byte order, so that every platform picks the same post.
+fn lowercase_ids(mut ids: Vec<String>) -> HashMap<String, String> {
+    ids.sort_by_cached_key(|id| (*id != id.to_lowercase(), id.clone()));
+
+    let mut lowercase_ids = HashMap::new();
+    for id in ids {
+        match lowercase_ids.entry(id.to_lowercase()) {
+            Entry::Vacant(entry) => {
+                entry.insert(id);
+            }
+            Entry::Occupied(entry) => {
+                warn!("Post ids {:?} and {id:?} differ only in case, so {id:?} is ignored", entry.get());
+            }
+        }
+    }
+    lowercase_ids
+}
+
+/// Whether another post's id differs from `id` only in case, and won over it
+fn is_shadowed(lowercase_ids: &HashMap<String, String>, id: &str) -> bool {
+    lowercase_ids.get(&id.to_lowercase()).is_some_and(|winner| winner != id)
+}
+
+/// Translations have ids like `my-post.fr`, while ids like `v1.2-release`
+/// or `notes.2024` have dots of their own
+/// Why `tag` makes a confusing name for its feed, at /tags/{tag}/rss.
+/// Anything else, slashes included, is percent-encoded into the URL.
+fn tag_problem(tag: &str) -> Option<&'static str> {
+    if tag.trim().is_empty() {
+        Some("is empty")
+    } else if tag.trim() != tag {
+        Some("starts or ends with whitespace")
+    } else if tag.contains(char::is_control) {
+        Some("contains a control character")
+    } else {
+        None
+    }
+}
+
+fn is_translation(id: &str) -> bool {
+    id.rsplit_once('.').is_some_and(|(_, lang)| is_language_tag(lang))
+}
+
+/// Whether `lang` looks like a language tag, e.g. `fr` or `pt-br`
+fn is_language_tag(lang: &str) -> bool {
+    let mut subtags = lang.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Summaries made up for posts are about as long as search engines show
+const GENERATED_SUMMARY_LEN: usize = 160;
+
+/// The first sentence of a post's first paragraph, as plain text
+fn generated_summary(body: &str) -> Option<String> {
+    // Headings, code and the like don't make for a summary
+    let paragraph = &body[body.find("<p>")?..];
+    let paragraph = &paragraph[..paragraph.find("</p>")?];
+
+    let text = text::strip_html(paragraph);
+    (!text.is_empty()).then(|| text::first_sentence(&text, GENERATED_SUMMARY_LEN))
+}
+
+/// Atom link to a feed's own URL, which feed validators expect
+fn atom_self_link(url: Url) -> AtomExtension {
+    let mut link = Link::default();
+    link.set_href(url);
+    link.set_rel("self");
+    link.set_mime_type(Some("application/rss+xml".to_string()));
+    AtomExtensionBuilder::default().links(vec![link]).build()
+}
+
+/// The id of the post that `id` is a translation of, or `id` itself
+pub fn untranslated_id(id: &str) -> &str {
+    match id.rsplit_once('.') {
+        Some((untranslated, lang)) if is_language_tag(lang) => untranslated,
+        _ => id,
+    }
+}
+
+/// `en` for `en-US`
+fn primary_language(lang: &str) -> &str {
+    lang.split(['-', '_']).next().unwrap_or(lang)
+}
+
 struct Parser<'a> {
     arena: Arena<Node<'a, RefCell<Ast>>>,
     buffer: String,
@@ -380,15 +980,16 @@ struct Parser<'a> {
 }
 
 impl<'a> Parser<'a> {
-    fn new(buffer: String) -> Self {
+    fn new(buffer: String, markdown: MarkdownOptions) -> Self {
         let mut options = ComrakOptions::default();
         options.extension.front_matter_delimiter = Some("---".into());
         options.extension.strikethrough = true;
         options.extension.header_ids = Some("".to_string());
         options.extension.table = true;
         options.extension.tasklist = true;
-        options.render.unsafe_ = true;
-        options.parse.smart = true;
+        options.render.unsafe_ = markdown.unsafe_html;
+        options.render.hardbreaks = markdown.hardbreaks;
+        options.parse.smart = markdown.smart;
         options.parse.relaxed_tasklist_matching = true;
 
         Self {
@@ -406,13 +1007,58 @@ impl<'a> Parser<'a> {
         ))
     }
 
-    fn generate_html(&self, root: &'a Node<'a, RefCell<Ast>>) -> Result<Vec<u8>, io::Error> {
+    fn generate_html(&self, root: &'a Node<'a, RefCell<Ast>>, sanitize: bool) -> Result<String, io::Error> {
         let mut html = vec![];
         comrak::format_html(root, &self.options, &mut html)?;
-        Ok(html)
+        let html = Self::convert_mermaid_blocks(&String::from_utf8_lossy(&html));
+
+        if sanitize {
+            Ok(Self::sanitize(&html))
+        } else {
+            Ok(html)
+        }
+    }
+
+    /// Removes scripts, event handlers, and anything else that isn't on an
+    /// allowlist of harmless tags and attributes.
+    fn sanitize(html: &str) -> String {
+        ammonia::Builder::default()
+            // Used by syntax highlighting, mermaid, and heading anchors
+            .add_generic_attributes(["class"])
+            .add_tag_attributes("a", ["id", "aria-hidden"])
+            // Task lists
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["type", "checked", "disabled"])
+            .clean(html)
+            .to_string()
     }
 
-    fn get_metadata(&self, root: &'a Node<'a, RefCell<Ast>>) -> Result<Metadata, io::Error> {
+    /// Replaces fenced `mermaid` code blocks with `<pre class="mermaid">`
+    /// elements, so mermaid.js can find and render them. This is done to
+    /// the rendered HTML because raw HTML nodes would be omitted when the
+    /// index sets `unsafe_html: false`.
+    fn convert_mermaid_blocks(html: &str) -> String {
+        const OPEN: &str = "<pre><code class=\"language-mermaid\">";
+        const CLOSE: &str = "</code></pre>";
+
+        let mut converted = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find(OPEN) {
+            let Some(len) = rest[start + OPEN.len()..].find(CLOSE) else {
+                break;
+            };
+            let diagram = &rest[start + OPEN.len()..][..len];
+            converted.push_str(&rest[..start]);
+            converted.push_str("<pre class=\"mermaid\">");
+            converted.push_str(diagram);
+            converted.push_str("</pre>");
+            rest = &rest[start + OPEN.len() + len + CLOSE.len()..];
+        }
+        converted.push_str(rest);
+        converted
+    }
+
+    fn get_metadata(&self, root: &'a Node<'a, RefCell<Ast>>, strict: bool) -> Result<Metadata, io::Error> {
         let front_matter = root
             .children()
             .filter_map(|child| {
@@ -432,7 +1078,7 @@ impl<'a> Parser<'a> {
                 .strip_suffix("---")
                 .unwrap();
 
-            Ok(Metadata::from_yaml(fm)?)
+            Ok(Metadata::from_yaml(fm, strict)?)
         } else {
             Err(io::Error::new(
                 ErrorKind::InvalidData,
@@ -444,6 +1090,7 @@ impl<'a> Parser<'a> {
     fn get_index_metadata(
         &self,
         root: &'a Node<'a, RefCell<Ast>>,
+        strict: bool,
     ) -> Result<IndexMetadata, io::Error> {
         let front_matter = root
             .children()
@@ -464,7 +1111,7 @@ impl<'a> Parser<'a> {
                 .strip_suffix("---")
                 .unwrap();
 
-            Ok(IndexMetadata::from_yaml(fm)?)
+            Ok(IndexMetadata::from_yaml(fm, strict)?)
         } else {
             Err(io::Error::new(
                 ErrorKind::InvalidData,
@@ -475,43 +1122,54 @@ impl<'a> Parser<'a> {
 }
 
 impl PostEntry {
-    pub async fn parse_index(mut file: File) -> Result<(Self, IndexMetadata), io::Error> {
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).await?;
-
-        let last_modified = file.metadata().await?.modified()?;
+    fn parse_index(
+        buffer: String,
+        last_modified: SystemTime,
+        options: ParseOptions,
+    ) -> Result<(Self, IndexMetadata), io::Error> {
+        let source = options.retain_source.then(|| buffer.clone());
+
+        // The index's own front matter decides how markdown is rendered, so
+        // it has to be read before the index itself can be rendered.
+        let metadata = {
+            let parser = Parser::new(buffer.clone(), options.markdown);
+            let root = parser.parse()?;
+            parser.get_index_metadata(root, options.strict_frontmatter)?
+        };
 
-        let parser = Parser::new(buffer);
+        let parser = Parser::new(buffer, metadata.markdown);
         let root = parser.parse()?;
-        let html = parser.generate_html(root)?;
-        let metadata = parser.get_index_metadata(root)?;
+        let html = parser.generate_html(root, options.sanitize_html)?;
 
         let entry = Self {
             updated: SystemTime::now(),
             last_modified,
             metadata: metadata.clone().into(),
-            body: String::from_utf8_lossy(&html).to_string(),
+            generated_summary: generated_summary(&html),
+            body: Some(html),
+            last_used: AtomicU64::new(0),
+            source,
         };
 
         Ok((entry, metadata))
     }
 
-    pub async fn parse(mut file: File) -> Result<Self, io::Error> {
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).await?;
-
-        let last_modified = file.metadata().await?.modified()?;
+    fn parse(buffer: String, last_modified: SystemTime, options: ParseOptions) -> Result<Self, io::Error> {
+        let source = options.retain_source.then(|| buffer.clone());
 
-        let parser = Parser::new(buffer);
+        let parser = Parser::new(buffer, options.markdown);
         let root = parser.parse()?;
-        let html = parser.generate_html(root)?;
-        let metadata = parser.get_metadata(root)?;
+        let html = parser.generate_html(root, options.sanitize_html)?;
+        let metadata = parser.get_metadata(root, options.strict_frontmatter)?;
 
         let entry = Self {
             updated: SystemTime::now(),
             last_modified,
             metadata: metadata.into(),
-            body: String::from_utf8_lossy(&html).to_string(),
+            generated_summary: generated_summary(&html),
+            body: Some(html),
+            last_used: AtomicU64::new(0),
+            source,
         };
 
         Ok(entry)
@@ -519,12 +1177,21 @@ impl PostEntry {
 }
 
 impl<'a> Post<'a> {
+    /// Orders posts by their `created` date. Posts without one are placed
+    /// by when their file was last modified, so they move up whenever the
+    /// file is touched, e.g. by a deploy; a warning is logged when such a
+    /// post is read. Posts published at the same moment are ordered by id,
+    /// so that listings don't shuffle between requests.
     pub fn cmp_published(&self, other: &Post) -> Ordering {
-        match (&self.entry.metadata.created, &other.entry.metadata.created) {
-            (None, None) => self.entry.last_modified.cmp(&other.entry.last_modified),
-            (None, Some(b)) => self.entry.last_modified.cmp(&b.system_time()),
-            (Some(a), None) => a.system_time().cmp(&other.entry.last_modified),
-            (Some(a), Some(b)) => a.system_time().cmp(&b.system_time()),
+        self.published_time()
+            .cmp(&other.published_time())
+            .then_with(|| self.id.cmp(other.id))
+    }
+
+    fn published_time(&self) -> SystemTime {
+        match &self.entry.metadata.created {
+            Some(created) => created.system_time(),
+            None => self.entry.last_modified,
         }
     }
 
@@ -532,35 +1199,65 @@ impl<'a> Post<'a> {
         self.id
     }
 
-    pub fn body(&self) -> &'a str {
-        &self.entry.body
+    /// When the post was created, or else when its file was last modified
+    pub fn published(&self) -> DateTime<FixedOffset> {
+        match &self.metadata().created {
+            Some(created) => created.fixed_offset(),
+            None => self.last_modified().fixed_offset(),
+        }
+    }
+
+    /// When the post's file was last modified
+    pub fn last_modified(&self) -> MyDateTime {
+        self.entry.last_modified.into()
+    }
+
+    /// The rendered post, unless it was evicted from the cache
+    pub fn body(&self) -> Option<&'a str> {
+        self.entry.body.as_deref()
     }
 
     pub fn metadata(&self) -> &'a Metadata {
         &self.entry.metadata
     }
 
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.entry.metadata.tags.iter().any(|t| t == tag)
+    }
+
+    /// The post's markdown, if the db retains it
+    pub fn source(&self) -> Option<&'a str> {
+        self.entry.source.as_deref()
+    }
+
     pub fn to_post_meta(&self) -> PostMeta {
         PostMeta {
             id: self.id().to_string(),
             title: self.metadata().title.to_string(),
-            summary: self.metadata().summary.as_ref().map(|s| s.to_string()),
+            summary: self.metadata().summary.clone()
+                .or_else(|| self.entry.generated_summary.clone()),
+            thumbnail: self.metadata().thumbnail.as_ref().map(|t| t.to_string()),
+            recently_updated: self.db.recently_updated_window().is_some_and(|window| {
+                is_recently_updated(self.entry.last_modified, self.metadata(), window, self.db.recently_updated_from_mtime())
+            }),
         }
     }
 
     pub fn to_post_content(&self) -> PostContent {
         PostContent {
             id: self.id().to_string(),
-            body: self.body().to_string(),
+            body: self.body().unwrap_or_default().to_string(),
             last_modified: self.entry.last_modified,
             metadata: self.metadata().clone(),
+            source: self.source().map(|s| s.to_string()),
         }
     }
 
-    pub fn to_rss_item(&self, include_content: bool) -> rss::Item {
+    /// `content` is the rendered post, if the item should include it
+    pub fn to_rss_item(&self, content: Option<&str>, summary_len: Option<usize>) -> Result<rss::Item, IndexNotLoaded> {
         use quick_xml::escape::partial_escape;
 
-        let url = self.db.post_url(self).to_string();
+        let url = self.db.post_url(self)?.to_string();
         let guid = rss::GuidBuilder::default()
             .value(url.clone())
             .permalink(true)
@@ -575,19 +1272,48 @@ impl<'a> Post<'a> {
         item.guid(Some(guid));
         item.description(
             self.metadata().summary.as_ref()
-                .map(|s| partial_escape(s).to_string()));
+                .map(|s| match summary_len {
+                    Some(len) => text::truncate_words(&text::strip_html(s), len),
+                    None => s.to_string(),
+                })
+                .map(|s| partial_escape(&s).to_string()));
 
-        if include_content {
+        if let Some(body) = content {
             item.content(Some(format!("{}{}",
-                util::render_base_part(self.db.site_url()),
-                self.body())));
+                util::render_base_part(self.db.site_url()?),
+                body)));
         }
 
-        item.build()
+        Ok(item.build())
     }
 }
 
 impl PostContent {
+    /// Parses a post that isn't in any db, with `site`'s markdown options
+    pub fn from_markdown(id: &str, markdown: &str, site: &IndexMetadata) -> Result<Self, io::Error> {
+        let options = ParseOptions {
+            markdown: site.markdown,
+            ..ParseOptions::default()
+        };
+        let entry = PostEntry::parse(markdown.to_string(), SystemTime::now(), options)?;
+
+        Ok(Self {
+            id: id.to_string(),
+            body: entry.body.unwrap_or_default(),
+            last_modified: entry.last_modified,
+            metadata: entry.metadata,
+            source: None,
+        })
+    }
+
+    /// The post's summary, or its first sentence if it doesn't have one
+    pub fn effective_summary(&self) -> Option<Cow<'_, str>> {
+        match &self.metadata.summary {
+            Some(summary) => Some(Cow::Borrowed(summary)),
+            None => generated_summary(&self.body).map(Cow::Owned),
+        }
+    }
+
     pub fn published(&self) -> DateTime<FixedOffset> {
         if let Some(created) = &self.metadata.created {
             created.fixed_offset()
@@ -599,4 +1325,302 @@ impl PostContent {
     pub fn last_modified(&self) -> DateTime<Local> {
         DateTime::from(self.last_modified)
     }
+
+    /// Whether the post was updated within `window` of now, see
+    /// [`PostDb::recently_updated_from_mtime`] for `from_mtime`
+    pub fn is_recently_updated(&self, window: Duration, from_mtime: bool) -> bool {
+        is_recently_updated(self.last_modified, &self.metadata, window, from_mtime)
+    }
+}
+
+/// Whether a post was updated within `window` of now, by its `updated` date,
+/// or else by `last_modified` if `from_mtime`. A post that hasn't been
+/// modified since its `created` date is new rather than updated.
+fn is_recently_updated(last_modified: SystemTime, metadata: &Metadata, window: Duration, from_mtime: bool) -> bool {
+    let updated = match &metadata.updated {
+        Some(updated) => updated.system_time(),
+        None if from_mtime => last_modified,
+        None => return false,
+    };
+    let edited = metadata.created.as_ref().is_none_or(|created| updated > created.system_time());
+    edited && updated + window >= SystemTime::now()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{atomic::{AtomicUsize, Ordering}, Arc},
+        time::{Duration, SystemTime},
+    };
+
+    use itertools::Itertools;
+
+    use super::{PostDb, RssOrder};
+    use crate::util::source::{ContentSource, MemoryContentSource, SourceFuture};
+
+    const INDEX: &str = "---\nurl: https://example.com\ntitle: Example\n---\nWelcome\n";
+
+    /// A db of the given posts, each modified a minute after the one before
+    fn db(posts: &[(&str, &str)]) -> PostDb {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut files = HashMap::from([("/index".to_string(), (INDEX.to_string(), start))]);
+        for (i, (id, markdown)) in posts.iter().enumerate() {
+            let modified = start + Duration::from_secs(60 * (i as u64 + 1));
+            files.insert(id.to_string(), (markdown.to_string(), modified));
+        }
+        PostDb::from_memory(files).unwrap()
+    }
+
+    /// The db's feed. Posts in memory are read without waiting, so the
+    /// future is simply run to completion.
+    fn feed(db: &PostDb, include_content: bool, order: RssOrder, tag: Option<&str>) -> rss::Channel {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(db.get_rss(None, include_content, None, 10, order, tag)).unwrap().build()
+    }
+
+    fn rss_titles(db: &PostDb, order: RssOrder, tag: Option<&str>) -> Vec<String> {
+        let channel = feed(db, false, order, tag);
+        channel.items().iter().map(|item| item.title().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn feed_order() {
+        let db = db(&[
+            ("old", "---\ntitle: old\ncreated: 1 Jan 2020 00:00 +0000\n---\nx\n"),
+            ("new", "---\ntitle: new\ncreated: 1 Jan 2022 00:00 +0000\n---\nx\n"),
+            ("mid", "---\ntitle: mid\ncreated: 1 Jan 2021 00:00 +0000\n---\nx\n"),
+        ]);
+
+        assert_eq!(rss_titles(&db, RssOrder::Published, None), ["new", "mid", "old"]);
+        assert_eq!(rss_titles(&db, RssOrder::Modified, None), ["mid", "new", "old"]);
+    }
+
+    #[test]
+    fn undated_posts_sort_by_modification() {
+        // Every file was modified in November 2023
+        let db = db(&[
+            ("undated", "---\ntitle: undated\n---\nx\n"),
+            ("dated", "---\ntitle: dated\ncreated: 1 Jan 2024 00:00 +0000\n---\nx\n"),
+            ("b", "---\ntitle: b\ncreated: 1 Jan 2020 00:00 +0000\n---\nx\n"),
+            ("a", "---\ntitle: a\ncreated: 1 Jan 2020 00:00 +0000\n---\nx\n"),
+        ]);
+
+        assert_eq!(rss_titles(&db, RssOrder::Published, None), ["dated", "undated", "b", "a"]);
+
+        let oldest_first = db.content_posts()
+            .sorted_by(|a, b| a.cmp_published(b))
+            .map(|post| post.id())
+            .collect::<Vec<_>>();
+        assert_eq!(oldest_first, ["a", "b", "undated", "dated"]);
+    }
+
+    #[test]
+    fn ids_differing_in_case() {
+        let db = db(&[
+            ("My-Post", "---\ntitle: upper\n---\nx\n"),
+            ("my-post", "---\ntitle: lower\n---\nx\n"),
+            ("Zed", "---\ntitle: Zed\n---\nx\n"),
+            ("ZED", "---\ntitle: ZED\n---\nx\n"),
+        ]);
+
+        // Lowercase wins, and otherwise the first in byte order
+        assert_eq!(db.canonical_id("MY-POST"), Some("my-post"));
+        assert_eq!(db.canonical_id("zed"), Some("ZED"));
+
+        let ids = db.content_posts().map(|post| post.id()).sorted().collect::<Vec<_>>();
+        assert_eq!(ids, ["ZED", "my-post"]);
+    }
+
+    #[test]
+    fn recently_updated_posts() {
+        let now = SystemTime::now();
+        let yesterday = chrono::Utc::now() - chrono::Duration::days(1);
+        let updated_yesterday = format!("updated: {}", yesterday.format("%e %b %Y %H:%M %z"));
+        let recently_updated = |settings: &str| {
+            let index = INDEX.replace("title: Example", &format!("title: Example\nrecently_updated_days: 7\n{settings}"));
+            let files = HashMap::from([
+                ("/index".to_string(), (index, now)),
+                ("old".to_string(), ("---\ntitle: old\n---\nx\n".to_string(), now - Duration::from_secs(8 * 24 * 60 * 60))),
+                // e.g. just checked out
+                ("touched".to_string(), ("---\ntitle: touched\ncreated: 1 Jan 2020 00:00 +0000\n---\nx\n".to_string(), now)),
+                ("edited".to_string(), (format!("---\ntitle: edited\ncreated: 1 Jan 2020 00:00 +0000\n{updated_yesterday}\n---\nx\n"), now)),
+                ("stale".to_string(), ("---\ntitle: stale\nupdated: 1 Jan 2021 00:00 +0000\n---\nx\n".to_string(), now)),
+                // Not modified since it was created
+                ("new".to_string(), ("---\ntitle: new\ncreated: 1 Jan 2999 00:00 +0000\n---\nx\n".to_string(), now)),
+            ]);
+            PostDb::from_memory(files).unwrap()
+                .content_posts()
+                .map(|post| post.to_post_meta())
+                .filter(|post| post.recently_updated)
+                .map(|post| post.id)
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(recently_updated(""), ["edited"]);
+        assert_eq!(recently_updated("recently_updated_from_mtime: true"), ["edited", "touched"]);
+    }
+
+    /// Counts how often posts are read from a [`MemoryContentSource`]
+    struct CountingSource(MemoryContentSource, Arc<AtomicUsize>);
+
+    impl ContentSource for CountingSource {
+        fn read<'a>(&'a self, id: &'a str) -> SourceFuture<'a, (Vec<u8>, SystemTime)> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.read(id)
+        }
+
+        fn modified<'a>(&'a self, id: &'a str) -> SourceFuture<'a, SystemTime> {
+            self.0.modified(id)
+        }
+
+        fn list(&self) -> SourceFuture<'_, Vec<String>> {
+            self.0.list()
+        }
+    }
+
+    #[test]
+    fn unchanged_posts_are_not_read_again() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let files = HashMap::from([("a".to_string(), ("---\ntitle: a\n---\nx\n".to_string(), SystemTime::UNIX_EPOCH))]);
+        let mut db = PostDb::new(Box::new(CountingSource(MemoryContentSource::new(files), reads.clone())), 0);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        for _ in 0..3 {
+            runtime.block_on(db.refresh("a")).unwrap();
+        }
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn evicted_posts_keep_summaries_and_content() {
+        let mut db = db(&[
+            ("a", "---\ntitle: a\n---\nAll about a.\n"),
+            ("b", "---\ntitle: b\n---\nAll about b.\n"),
+        ]);
+        db.set_max_cached_posts(1);
+        db.evict();
+        assert_eq!(db.content_posts().filter(|post| post.body().is_none()).count(), 1);
+
+        let summaries = db.content_posts()
+            .map(|post| post.to_post_meta().summary)
+            .sorted()
+            .collect_vec();
+        assert_eq!(summaries, [Some("All about a.".to_string()), Some("All about b.".to_string())]);
+
+        let channel = feed(&db, true, RssOrder::Published, None);
+        for item in channel.items() {
+            assert!(item.content().is_some_and(|content| content.contains("All about")), "{:?}", item.title());
+        }
+        // Rendering for the feed doesn't fill the cache again
+        assert_eq!(db.content_posts().filter(|post| post.body().is_none()).count(), 1);
+    }
+
+    #[test]
+    fn mermaid_without_unsafe_html() {
+        let index = "---\nurl: https://example.com\ntitle: Example\nmarkdown: { unsafe_html: false }\n---\nWelcome\n";
+        let db = db(&[
+            ("/index", index),
+            ("chart", "---\ntitle: chart\nmermaid: true\n---\n```mermaid\ngraph TD; A-->B\n```\n\n<b>raw</b>\n"),
+        ]);
+
+        let body = db.get("chart").unwrap().body().unwrap();
+        assert!(body.contains("<pre class=\"mermaid\">graph TD; A--&gt;B\n</pre>"), "{body}");
+        assert!(!body.contains("<b>raw</b>"));
+    }
+
+    #[test]
+    fn feed_tag_filter() {
+        let db = db(&[
+            ("a", "---\ntitle: a\ntags: [rust]\n---\nx\n"),
+            ("b", "---\ntitle: b\ntags: [go]\n---\nx\n"),
+        ]);
+
+        assert!(db.has_tag("rust"));
+        assert!(!db.has_tag("zig"));
+        assert_eq!(rss_titles(&db, RssOrder::Modified, Some("rust")), ["a"]);
+    }
+
+    #[test]
+    fn feed_always_has_description() {
+        let describe = |index: &str| {
+            let files = HashMap::from([("/index".to_string(), (index.to_string(), SystemTime::now()))]);
+            let db = PostDb::from_memory(files).unwrap();
+            let channel = feed(&db, false, RssOrder::Published, None);
+            assert!(channel.to_string().contains("<description>"));
+            channel.description().to_string()
+        };
+
+        assert_eq!(describe(INDEX), "Example");
+        assert_eq!(describe("---\nurl: https://x.com\ntitle: X\nsummary: About X\n---\n"), "About X");
+        assert_eq!(
+            describe("---\nurl: https://x.com\ntitle: X\nsummary: About X\nrss_description: Feed of X\n---\n"),
+            "Feed of X",
+        );
+    }
+
+    #[test]
+    fn podcast_channel_tags() {
+        let index = concat!(
+            "---\nurl: https://x.com\ntitle: X\nauthor: Me\n",
+            "podcast:\n  categories: [Arts/Books, Technology]\n  image: cover.jpg\n",
+            "  owner:\n    email: me@x.com\n---\n",
+        );
+        let files = HashMap::from([("/index".to_string(), (index.to_string(), SystemTime::now()))]);
+        let db = PostDb::from_memory(files).unwrap();
+        let xml = feed(&db, false, RssOrder::Published, None).to_string();
+
+        assert!(xml.contains("xmlns:itunes="));
+        assert!(xml.contains("<itunes:author>Me</itunes:author>"));
+        assert!(xml.contains(r#"<itunes:category text="Arts"><itunes:category text="Books"></itunes:category></itunes:category>"#));
+        assert!(xml.contains(r#"<itunes:image href="https://x.com/public/cover.jpg"/>"#));
+        assert!(xml.contains("<itunes:explicit>false</itunes:explicit>"));
+        assert!(xml.contains("<itunes:email>me@x.com</itunes:email>"));
+
+        // Plain blogs don't get the namespace
+        let plain = feed(&self::db(&[]), false, RssOrder::Published, None);
+        assert!(!plain.to_string().contains("itunes"));
+    }
+
+    #[test]
+    fn series_and_pinned_order() {
+        let db = db(&[
+            ("two", "---\ntitle: two\nseries: s\nseries_order: 2\npinned: true\n---\nx\n"),
+            ("one", "---\ntitle: one\nseries: s\nseries_order: 1\npinned: true\nweight: 5\n---\nx\n"),
+            ("other", "---\ntitle: other\n---\nx\n"),
+        ]);
+
+        let series = db.series_members("s").map(|post| post.id()).collect::<Vec<_>>();
+        assert_eq!(series, ["one", "two"]);
+        let pinned = db.pinned_posts().map(|post| post.id()).collect::<Vec<_>>();
+        assert_eq!(pinned, ["one", "two"]);
+    }
+
+    #[test]
+    fn drafts_and_translations_are_not_listed() {
+        let mut db = db(&[
+            ("post", "---\ntitle: post\n---\nx\n"),
+            ("post.fr", "---\ntitle: poste\n---\nx\n"),
+            ("wip", "---\ntitle: wip\ndraft: true\n---\nx\n"),
+            ("v1.2-release", "---\ntitle: v1.2\ncreated: 1 Jan 2020 00:00 +0000\n---\nx\n"),
+        ]);
+
+        // Without language negotiation, a dotted id is just an id
+        let ids = db.content_posts().map(|post| post.id()).sorted().collect::<Vec<_>>();
+        assert_eq!(ids, ["post", "post.fr", "v1.2-release"]);
+
+        db.set_translations(true);
+        let ids = db.content_posts().map(|post| post.id()).sorted().collect::<Vec<_>>();
+        assert_eq!(ids, ["post", "v1.2-release"]);
+        assert_eq!(db.translations("post").collect::<Vec<_>>(), ["fr"]);
+        assert!(db.translations("v1").next().is_none());
+        assert_eq!(db.canonical_id("POST"), Some("post"));
+
+        // The draft is newer, but never counted
+        let published = db.content_posts().find(|post| post.id() == "post").unwrap().published();
+        db.set_show_drafts(true);
+        assert_eq!(db.post_count(), 2);
+        assert_eq!(db.latest_post_date(), Some(published));
+    }
 }
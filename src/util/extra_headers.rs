@@ -0,0 +1,119 @@
+//! Response headers added from the `--extra-headers` file, e.g. for a CDN.
+
+use std::{collections::HashMap, error::Error, path::Path};
+
+use hyper::{header::{HeaderName, HeaderValue}, HeaderMap};
+use serde::Deserialize;
+
+/// The kinds of responses that extra headers can be added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RouteClass {
+    /// The homepage, and the list of posts if it has its own path
+    Index,
+    /// Posts, including /random
+    Post,
+    /// The archive, series and subscribe pages, and /posts.json
+    Listing,
+    /// The RSS feeds
+    Feed,
+    /// Files in the public directory
+    Asset,
+}
+
+impl RouteClass {
+    /// Classifies a request path. `is_posts_path` is true when the path is
+    /// the one given with `--posts-path`.
+    pub fn of(path: &str, is_posts_path: bool) -> Option<Self> {
+        if path == "/" || is_posts_path {
+            Some(Self::Index)
+        } else if path.starts_with("/p/") || path.starts_with("/random") {
+            Some(Self::Post)
+        } else if path.starts_with("/archive") || path.starts_with("/series/") || path == "/posts.json" || path == "/subscribe" {
+            Some(Self::Listing)
+        } else if path == "/rss" || path == "/rss/all" || (path.starts_with("/tags/") && path.ends_with("/rss")) {
+            Some(Self::Feed)
+        } else if path.starts_with("/public/") {
+            Some(Self::Asset)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExtraHeaders(HashMap<RouteClass, HeaderMap>);
+
+impl ExtraHeaders {
+    /// Reads a YAML file mapping route classes to header names and values:
+    ///
+    /// ```yaml
+    /// post:
+    ///   CDN-Cache-Control: max-age=600
+    ///   Surrogate-Key: posts
+    /// ```
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|err| format!("Reading {path:?}: {err}"))?;
+        Self::from_yaml(&config).map_err(|err| format!("Parsing {path:?}: {err}").into())
+    }
+
+    fn from_yaml(yaml: &str) -> Result<Self, Box<dyn Error>> {
+        let config: HashMap<RouteClass, HashMap<String, String>> = serde_yaml::from_str(yaml)?;
+
+        let classes = config
+            .into_iter()
+            .map(|(class, headers)| {
+                let headers = headers
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let name = HeaderName::from_bytes(name.as_bytes())
+                            .map_err(|_| format!("Invalid header name {name:?}"))?;
+                        let value = HeaderValue::from_str(&value)
+                            .map_err(|_| format!("Invalid value for header {name}: {value:?}"))?;
+                        Ok((name, value))
+                    })
+                    .collect::<Result<HeaderMap, String>>()?;
+                Ok((class, headers))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self(classes))
+    }
+
+    /// Adds the headers for `class` to `headers`, replacing any that the
+    /// response already has.
+    pub fn apply(&self, class: RouteClass, headers: &mut HeaderMap) {
+        if let Some(extra) = self.0.get(&class) {
+            for (name, value) in extra {
+                headers.insert(name, value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::HeaderMap;
+
+    use super::{ExtraHeaders, RouteClass};
+
+    #[test]
+    fn apply_to_class() {
+        let extra = ExtraHeaders::from_yaml("post:\n  Surrogate-Key: posts\n").unwrap();
+
+        let mut headers = HeaderMap::new();
+        extra.apply(RouteClass::Asset, &mut headers);
+        assert!(headers.is_empty());
+
+        extra.apply(RouteClass::Post, &mut headers);
+        assert_eq!(headers.get("surrogate-key").unwrap(), "posts");
+    }
+
+    #[test]
+    fn reject_invalid_headers() {
+        assert!(ExtraHeaders::from_yaml("post:\n  Bad Name: x\n").is_err());
+        assert!(ExtraHeaders::from_yaml("post:\n  X-Ok: \"line\\nbreak\"\n").is_err());
+        assert!(ExtraHeaders::from_yaml("nowhere:\n  X-Ok: x\n").is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! Keeps the blog directory up to date with a remote git repository.
+
+use std::{path::PathBuf, time::Duration};
+
+use log::{debug, error, warn};
+use tokio::process::Command;
+
+/// How long a single `git pull` may take before it is abandoned
+const PULL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Runs `git pull` in `dir` every `interval`, forever.
+pub async fn pull_periodically(dir: PathBuf, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        pull(&dir).await;
+    }
+}
+
+async fn pull(dir: &PathBuf) {
+    debug!("Running git pull in {dir:?}");
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["pull", "--ff-only", "--quiet"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(PULL_TIMEOUT, output).await {
+        Ok(Ok(output)) if output.status.success() => {
+            debug!("Pulled {dir:?}");
+        }
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("git pull in {dir:?} failed ({}): {}", output.status, stderr.trim());
+        }
+        Ok(Err(err)) => error!("Could not run git pull in {dir:?}: {err}"),
+        Err(_) => warn!("git pull in {dir:?} timed out"),
+    }
+}
@@ -1,33 +1,123 @@
 use std::str::Split;
 
 use chrono::{FixedOffset, DateTime, TimeZone};
-use hyper::{HeaderMap, header::{HeaderValue, IF_MODIFIED_SINCE, CACHE_CONTROL}};
+use hyper::{HeaderMap, header::{HeaderValue, ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, CACHE_CONTROL, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE}};
 
 pub trait HeaderExt {
     fn if_modified_since(&self) -> Option<IfModifiedSince>;
     fn cache_control<'a>(&'a self) -> Option<CacheControl<'a>>;
     fn accepted_manipulations<'a>(&'a self) -> Option<AcceptedManipulations<'a>>;
+    fn accepted_languages(&self) -> Vec<String>;
+    fn bearer_token(&self) -> Option<&str>;
+    fn range(&self) -> Option<ByteRange>;
+    fn if_none_match(&self) -> Option<&str>;
+    fn if_range(&self) -> Option<&str>;
+    fn is_fragment_request(&self) -> bool;
+    fn prefers_feed(&self) -> bool;
 
-    fn is_cache_valid<TZ>(&self, current: &DateTime<TZ>) -> bool
+    /// Evaluates the request's conditional headers against the current
+    /// representation, in the order given by RFC 7232, section 6.
+    /// `etag` is the representation's entity tag, quotes included.
+    ///
+    /// If-None-Match takes precedence over If-Modified-Since. A Range is
+    /// only honored if If-Range, when present, still matches. `skew` is how
+    /// many seconds `last_modified` may be after If-Modified-Since, see
+    /// [`IfModifiedSince::is_up_to_date`].
+    fn conditional<TZ>(&self, etag: Option<&str>, last_modified: &DateTime<TZ>, skew: u32) -> Conditional
     where
         TZ: TimeZone
     {
         let no_cache = self.cache_control()
             .map_or(false, |cc| cc.is_no_cache());
-        let cache_valid = self.if_modified_since()
-            .map_or(false, |ifs| ifs.is_up_to_date(current));
 
-        cache_valid && !no_cache
+        if !no_cache {
+            let not_modified = match self.if_none_match() {
+                Some(tags) => etag.is_some_and(|etag| {
+                    tags.trim() == "*" || tags.split(',').any(|tag| weak_eq(tag.trim(), etag))
+                }),
+                None => self.if_modified_since()
+                    .map_or(false, |ims| ims.is_up_to_date(last_modified, skew)),
+            };
+
+            if not_modified {
+                return Conditional::NotModified;
+            }
+        }
+
+        let Some(range) = self.range() else {
+            return Conditional::Full;
+        };
+
+        let range_applies = match self.if_range() {
+            None => true,
+            // Only strong validators may be used with If-Range
+            Some(tag) if tag.starts_with('"') => etag == Some(tag),
+            Some(date) => DateTime::parse_from_rfc2822(date)
+                .is_ok_and(|date| date.timestamp() == last_modified.timestamp()),
+        };
+
+        if range_applies {
+            Conditional::Partial(range)
+        } else {
+            Conditional::Full
+        }
     }
 }
 
+/// What a response should contain, according to the request's conditional
+/// headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conditional {
+    /// The whole representation
+    Full,
+    /// Nothing, because the client's copy is current
+    NotModified,
+    /// Only the requested range of bytes
+    Partial(ByteRange),
+}
+
+/// A single range from a `Range: bytes=...` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `first-last` or `first-`, inclusive
+    From(u64, Option<u64>),
+    /// `-len`, the last `len` bytes
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// The inclusive start and end of the range within `len` bytes, or
+    /// `None` if none of the range is within them.
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::From(first, last) if first < len => {
+                Some((first, last.map_or(len - 1, |last| last.min(len - 1))))
+            }
+            ByteRange::Suffix(suffix) if suffix > 0 && len > 0 => {
+                Some((len.saturating_sub(suffix), len - 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Weak comparison of entity tags (RFC 7232, section 2.3.2)
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
 pub struct IfModifiedSince(DateTime<FixedOffset>);
 impl IfModifiedSince {
-    fn is_up_to_date<TZ>(&self, current: &DateTime<TZ>) -> bool
+    /// Whether a representation last modified at `current` is no newer than
+    /// the client's copy. HTTP dates have no fractions of a second, so both
+    /// sides are truncated to whole seconds; otherwise a file modified at
+    /// .4s past the second would seem newer than a copy from that second.
+    /// `skew` allows for the client's clock being that many seconds behind.
+    fn is_up_to_date<TZ>(&self, current: &DateTime<TZ>, skew: u32) -> bool
     where
         TZ: TimeZone
     {
-        current <= &(self.0 + chrono::Duration::seconds(1))
+        current.timestamp() <= self.0.timestamp() + i64::from(skew)
     }
 
     pub fn as_datetime(&self) -> &DateTime<FixedOffset> {
@@ -72,6 +162,99 @@ impl HeaderExt for HeaderMap<HeaderValue> {
         let spl = text.split(SEPARATORS);
         Some(AcceptedManipulations(spl))
     }
+
+    /// Languages from Accept-Language, most preferred first
+    fn accepted_languages(&self) -> Vec<String> {
+        let Some(text) = self.get(ACCEPT_LANGUAGE).and_then(|value| value.to_str().ok()) else {
+            return vec![];
+        };
+
+        let mut languages: Vec<(String, f32)> = text
+            .split(',')
+            .filter_map(|item| {
+                let mut params = item.split(';').map(str::trim);
+                let lang = params.next().filter(|lang| !lang.is_empty() && *lang != "*")?;
+                let quality = params
+                    .find_map(|param| param.strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+                (quality > 0.0).then(|| (lang.to_lowercase(), quality))
+            })
+            .collect();
+
+        // Stable, so equally preferred languages keep their order
+        languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+        languages.into_iter().map(|(lang, _)| lang).collect()
+    }
+
+    /// A single byte range. Several ranges are not supported, and treated
+    /// like no range at all.
+    fn range(&self) -> Option<ByteRange> {
+        let text = self.get(RANGE)?.to_str().ok()?;
+        let spec = text.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+
+        let (first, last) = spec.split_once('-')?;
+        let (first, last) = (first.trim(), last.trim());
+        if first.is_empty() {
+            Some(ByteRange::Suffix(last.parse().ok()?))
+        } else {
+            let first = first.parse().ok()?;
+            let last = if last.is_empty() { None } else { Some(last.parse().ok()?) };
+            last.is_none_or(|last| first <= last).then_some(ByteRange::From(first, last))
+        }
+    }
+
+    fn if_none_match(&self) -> Option<&str> {
+        self.get(IF_NONE_MATCH)?.to_str().ok()
+    }
+
+    fn if_range(&self) -> Option<&str> {
+        self.get(IF_RANGE)?.to_str().ok().map(str::trim)
+    }
+
+    /// Whether HTMX sent the request, to swap only the page's content in
+    fn is_fragment_request(&self) -> bool {
+        self.get("HX-Request").is_some_and(|value| value == "true")
+    }
+
+    /// Whether Accept ranks a feed above HTML, as feed readers that probe
+    /// a homepage do. Browsers always accept HTML at least as much as
+    /// anything else, so this is false for them.
+    fn prefers_feed(&self) -> bool {
+        let Some(text) = self.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+            return false;
+        };
+
+        let mut feed = 0.0_f32;
+        let mut html = 0.0_f32;
+        for item in text.split(',') {
+            let mut params = item.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default().to_lowercase();
+            let Some(quality) = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())
+            else {
+                continue;
+            };
+
+            match media_type.as_str() {
+                "application/rss+xml" | "application/atom+xml" => feed = feed.max(quality),
+                "text/html" | "application/xhtml+xml" | "text/*" | "*/*" => html = html.max(quality),
+                _ => {}
+            }
+        }
+
+        feed > html
+    }
+
+    /// The token from `Authorization: Bearer <token>`
+    fn bearer_token(&self) -> Option<&str> {
+        let text = self.get(AUTHORIZATION)?.to_str().ok()?;
+        let (scheme, token) = text.split_once(' ')?;
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+    }
 }
 
 // Header value 'separators' according to RFC 2616
@@ -84,3 +267,99 @@ const SEPARATORS: [char; 19] = [
 // fn is_ctl(c: char) -> bool {
 //     c.is_ascii() && (c as u8 > 31) && (c as u8 != 127)
 // }
+
+#[cfg(test)]
+mod test {
+    use chrono::DateTime;
+    use hyper::{HeaderMap, header::{HeaderName, ACCEPT, ACCEPT_LANGUAGE, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE}};
+
+    use super::{ByteRange, Conditional, HeaderExt};
+
+    #[test]
+    fn accepted_languages_by_quality() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_LANGUAGE, "fr;q=0.5, en-US, de;q=0, *;q=0.1, EN;q=0.9".parse().unwrap());
+        assert_eq!(headers.accepted_languages(), ["en-us", "en", "fr"]);
+    }
+
+    #[test]
+    fn accepted_languages_missing() {
+        assert!(HeaderMap::new().accepted_languages().is_empty());
+    }
+
+    fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.clone(), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn feed_preferred_over_html() {
+        let prefers_feed = |accept: &str| headers(&[(ACCEPT, accept)]).prefers_feed();
+
+        // Browsers
+        assert!(!prefers_feed("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"));
+        assert!(!prefers_feed("*/*"));
+        assert!(!HeaderMap::new().prefers_feed());
+        // Feed readers
+        assert!(prefers_feed("application/rss+xml"));
+        assert!(prefers_feed("application/rss+xml, application/atom+xml, */*;q=0.1"));
+        assert!(prefers_feed("application/atom+xml, text/html;q=0.5"));
+        // A tie goes to HTML
+        assert!(!prefers_feed("application/rss+xml, text/html"));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let modified = DateTime::parse_from_rfc2822("Mon, 28 Aug 2023 18:00:00 +0000").unwrap();
+        let ims = (IF_MODIFIED_SINCE, "Mon, 28 Aug 2023 18:00:00 +0000");
+
+        assert_eq!(headers(std::slice::from_ref(&ims)).conditional(Some("\"a\""), &modified, 1), Conditional::NotModified);
+        assert_eq!(
+            headers(&[ims.clone(), (IF_NONE_MATCH, "\"b\"")]).conditional(Some("\"a\""), &modified, 1),
+            Conditional::Full
+        );
+        assert_eq!(
+            headers(&[ims, (IF_NONE_MATCH, "\"b\", W/\"a\"")]).conditional(Some("\"a\""), &modified, 1),
+            Conditional::NotModified
+        );
+    }
+
+    #[test]
+    fn if_modified_since_to_the_second() {
+        let ims = headers(&[(IF_MODIFIED_SINCE, "Mon, 28 Aug 2023 18:00:00 +0000")]);
+        let at = |time: &str| DateTime::parse_from_rfc3339(&format!("2023-08-28T{time}+00:00")).unwrap();
+
+        assert_eq!(ims.conditional(None, &at("18:00:00.4"), 0), Conditional::NotModified);
+        assert_eq!(ims.conditional(None, &at("18:00:00.999"), 0), Conditional::NotModified);
+        assert_eq!(ims.conditional(None, &at("18:00:01"), 0), Conditional::Full);
+        assert_eq!(ims.conditional(None, &at("18:00:01.9"), 1), Conditional::NotModified);
+        assert_eq!(ims.conditional(None, &at("18:00:02"), 1), Conditional::Full);
+        assert_eq!(ims.conditional(None, &at("17:59:59.9"), 0), Conditional::NotModified);
+    }
+
+    #[test]
+    fn range_needs_matching_if_range() {
+        let modified = DateTime::parse_from_rfc2822("Mon, 28 Aug 2023 18:00:00 +0000").unwrap();
+        let range = (RANGE, "bytes=10-");
+        let partial = Conditional::Partial(ByteRange::From(10, None));
+
+        assert_eq!(headers(std::slice::from_ref(&range)).conditional(Some("\"a\""), &modified, 1), partial);
+        assert_eq!(headers(&[range.clone(), (IF_RANGE, "\"a\"")]).conditional(Some("\"a\""), &modified, 1), partial);
+        assert_eq!(headers(&[range.clone(), (IF_RANGE, "\"b\"")]).conditional(Some("\"a\""), &modified, 1), Conditional::Full);
+        assert_eq!(
+            headers(&[range, (IF_RANGE, "Sun, 27 Aug 2023 18:00:00 +0000")]).conditional(Some("\"a\""), &modified, 1),
+            Conditional::Full
+        );
+    }
+
+    #[test]
+    fn parse_and_resolve_ranges() {
+        assert_eq!(headers(&[(RANGE, "bytes=0-4")]).range(), Some(ByteRange::From(0, Some(4))));
+        assert_eq!(headers(&[(RANGE, "bytes=-3")]).range(), Some(ByteRange::Suffix(3)));
+        assert_eq!(headers(&[(RANGE, "bytes=0-1, 4-5")]).range(), None);
+        assert_eq!(headers(&[(RANGE, "bytes=5-1")]).range(), None);
+
+        assert_eq!(ByteRange::From(0, Some(99)).resolve(10), Some((0, 9)));
+        assert_eq!(ByteRange::Suffix(3).resolve(10), Some((7, 9)));
+        assert_eq!(ByteRange::From(10, None).resolve(10), None);
+    }
+}
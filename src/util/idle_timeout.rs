@@ -0,0 +1,80 @@
+//! Closes connections that have been idle for too long, which hyper's
+//! server can't do by itself for keep-alive connections.
+
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::server::conn::AddrStream;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{sleep, Instant, Sleep},
+};
+
+/// A connection that fails with [`io::ErrorKind::TimedOut`] once nothing
+/// has been read or written for `timeout`. Without a timeout, it is just
+/// the connection.
+pub struct IdleTimeout {
+    inner: AddrStream,
+    timeout: Option<Duration>,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl IdleTimeout {
+    pub fn new(inner: AddrStream, timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            timeout,
+            deadline: timeout.map(|timeout| Box::pin(sleep(timeout))),
+        }
+    }
+
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.inner.remote_addr()
+    }
+
+    /// Pushes the deadline back after any progress, or fails if it passed
+    fn check<T>(&mut self, cx: &mut Context<'_>, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        let (Some(timeout), Some(deadline)) = (self.timeout, self.deadline.as_mut()) else {
+            return poll;
+        };
+
+        match poll {
+            Poll::Ready(result) => {
+                deadline.as_mut().reset(Instant::now() + timeout);
+                Poll::Ready(result)
+            }
+            Poll::Pending => match deadline.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Err(io::ErrorKind::TimedOut.into())),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+impl AsyncRead for IdleTimeout {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.check(cx, poll)
+    }
+}
+
+impl AsyncWrite for IdleTimeout {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.check(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,118 @@
+//! Permanent redirects from the URLs of a blog's previous platform to its
+//! posts, from the `--legacy-redirects` file.
+
+use std::{collections::HashMap, error::Error, path::Path};
+
+use log::warn;
+
+/// A legacy URL with a query, like WordPress's `/?p=123`
+#[derive(Debug, Clone)]
+struct QueryPattern {
+    path: String,
+    params: Vec<(String, String)>,
+    id: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct LegacyRedirects {
+    /// Post ids by legacy path, for URLs without a query
+    paths: HashMap<String, String>,
+    queries: Vec<QueryPattern>,
+}
+
+impl LegacyRedirects {
+    /// Reads a YAML file mapping legacy URLs to post ids:
+    ///
+    /// ```yaml
+    /// /?p=123: my-post
+    /// /2019/05/old-slug/: my-post
+    /// ```
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|err| format!("Reading {path:?}: {err}"))?;
+        Self::from_yaml(&config).map_err(|err| format!("Parsing {path:?}: {err}").into())
+    }
+
+    fn from_yaml(yaml: &str) -> Result<Self, Box<dyn Error>> {
+        let config: HashMap<String, String> = serde_yaml::from_str(yaml)?;
+
+        let mut redirects = Self::default();
+        for (url, id) in config {
+            if !url.starts_with('/') {
+                return Err(format!("Legacy URL {url:?} is not an absolute path").into());
+            }
+
+            match url.split_once('?') {
+                Some((path, query)) => redirects.queries.push(QueryPattern {
+                    path: normalize(path).to_string(),
+                    params: url::form_urlencoded::parse(query.as_bytes()).into_owned().collect(),
+                    id,
+                }),
+                None => {
+                    redirects.paths.insert(normalize(&url).to_string(), id);
+                }
+            }
+        }
+
+        Ok(redirects)
+    }
+
+    /// The id of the post that a request for `path` and `query` should be
+    /// redirected to. A query pattern matches if the request has each of its
+    /// parameters, whatever else it has, like tracking parameters.
+    pub fn lookup(&self, path: &str, query: Option<&str>) -> Option<&str> {
+        let path = normalize(path);
+
+        if let Some(query) = query {
+            let params = url::form_urlencoded::parse(query.as_bytes()).collect::<Vec<_>>();
+            let has = |key: &str, value: Option<&str>| {
+                params.iter().any(|(k, v)| k == key && value.is_none_or(|value| v == value))
+            };
+
+            let mut looks_legacy = false;
+            for pattern in self.queries.iter().filter(|pattern| pattern.path == path) {
+                if pattern.params.iter().all(|(key, value)| has(key, Some(value))) {
+                    return Some(&pattern.id);
+                }
+                looks_legacy |= pattern.params.iter().all(|(key, _)| has(key, None));
+            }
+
+            // e.g. a post that was left out of the file
+            if looks_legacy {
+                warn!("No legacy redirect for {path}?{query}");
+            }
+        }
+
+        self.paths.get(path).map(|id| id.as_str())
+    }
+}
+
+/// Ignores a trailing slash, which most platforms add to every URL
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LegacyRedirects;
+
+    #[test]
+    fn query_and_path_patterns() {
+        let redirects = LegacyRedirects::from_yaml("/?p=123: hello\n/2019/05/old-slug/: old\n").unwrap();
+
+        assert_eq!(redirects.lookup("/", Some("p=123")), Some("hello"));
+        assert_eq!(redirects.lookup("/", Some("utm_source=x&p=123")), Some("hello"));
+        assert_eq!(redirects.lookup("/", Some("p=124")), None);
+        assert_eq!(redirects.lookup("/", None), None);
+        assert_eq!(redirects.lookup("/2019/05/old-slug", None), Some("old"));
+        assert_eq!(redirects.lookup("/2019/05/old-slug/", Some("replytocom=1")), Some("old"));
+    }
+
+    #[test]
+    fn reject_relative_urls() {
+        assert!(LegacyRedirects::from_yaml("p/1: hello\n").is_err());
+    }
+}
@@ -1,10 +1,20 @@
 use dioxus::prelude::VirtualDom;
 use url::Url;
 
+pub mod body;
 pub mod db;
+pub mod extra_headers;
+pub mod git_pull;
 pub mod mydatetime;
 pub mod header_ext;
 pub mod has_any_symlinks;
+pub mod idle_timeout;
+pub mod legacy_redirects;
+pub mod sitemap;
+pub mod source;
+pub mod text;
+pub mod theme;
+pub mod webhook;
 
 pub fn render_html(mut vdom: VirtualDom, lang: &str) -> String {
     let _ = vdom.rebuild();
@@ -14,6 +24,21 @@ pub fn render_html(mut vdom: VirtualDom, lang: &str) -> String {
     format!("<!DOCTYPE html><html lang=\"{lang}\">{}</html>", renderer.render(&vdom))
 }
 
+/// Renders `vdom` without the surrounding document, for pages requested as
+/// fragments by client-side navigation.
+pub fn render_fragment(mut vdom: VirtualDom) -> String {
+    let _ = vdom.rebuild();
+    let mut renderer = dioxus_ssr::Renderer::new();
+    renderer.sanitize = true;
+    renderer.render(&vdom)
+}
+
+/// Path of the listing page for a series.
+pub fn series_path(series: &str) -> String {
+    let series = percent_encoding::utf8_percent_encode(series, percent_encoding::NON_ALPHANUMERIC);
+    format!("/series/{series}")
+}
+
 pub fn render_base_part(url: &Url) -> String {
     let href = html_escape::encode_unquoted_attribute(url.as_str());
     format!("<base href=\"{href}\" />")
@@ -10,6 +10,9 @@ pub struct MyDateTime(DateTime<FixedOffset>);
 const DISPLAY_FORMAT: &str = "%e %b %Y %H:%M:%S %z";
 const NO_SECONDS_24_FORMAT: &str = "%e %b %Y %H:%M %z";
 
+/// A valid datetime, shown to authors when they get one wrong
+const EXAMPLE: &str = "28 Aug 2023 18:00 +0500";
+
 const ALLOWED_FORMATS: [&'static str; 8] = [
     "%e %b %Y %I:%M:%S %p %z",
     DISPLAY_FORMAT,
@@ -65,11 +68,14 @@ impl<'de> serde::Deserialize<'de> for MyDateTime {
         for format in ALLOWED_FORMATS.iter() {
             let parsed = DateTime::<FixedOffset>::parse_from_str(&s, format);
             if let Ok(parsed) = parsed {
-                return Ok(MyDateTime(parsed.into()));
+                return Ok(MyDateTime(parsed));
             }
         }
 
-        Err(D::Error::custom(format!("Invalid datetime format")))
+        Err(D::Error::custom(format!(
+            "invalid datetime {s:?}, expected something like \"{EXAMPLE}\" (accepted formats: {})",
+            ALLOWED_FORMATS.join(", ")
+        )))
     }
 }
 
@@ -86,6 +92,12 @@ impl From<DateTime<Local>> for MyDateTime {
     }
 }
 
+impl From<DateTime<FixedOffset>> for MyDateTime {
+    fn from(value: DateTime<FixedOffset>) -> Self {
+        Self(value)
+    }
+}
+
 impl From<SystemTime> for MyDateTime {
     fn from(value: SystemTime) -> Self {
         Self::from(DateTime::<Local>::from(value))
@@ -94,8 +106,19 @@ impl From<SystemTime> for MyDateTime {
 
 #[cfg(test)]
 mod test {
-    use super::ALLOWED_FORMATS;
+    use super::{MyDateTime, ALLOWED_FORMATS};
     use chrono::{DateTime, FixedOffset};
+    use serde::Deserialize;
+
+    #[test]
+    fn invalid_datetime_message() {
+        let deserializer = serde_yaml::Deserializer::from_str("2023-08-28");
+        let err = MyDateTime::deserialize(deserializer).unwrap_err().to_string();
+
+        assert!(err.contains("\"2023-08-28\""), "{err}");
+        assert!(err.contains("28 Aug 2023 18:00 +0500"), "{err}");
+        assert!(err.contains(ALLOWED_FORMATS[0]), "{err}");
+    }
 
     #[test]
     fn parse_format_1() {
@@ -0,0 +1,46 @@
+//! Sitemaps, following https://www.sitemaps.org/protocol.html
+
+use chrono::SecondsFormat;
+use quick_xml::escape::escape;
+use url::Url;
+
+use super::mydatetime::MyDateTime;
+
+pub struct SitemapUrl {
+    pub loc: Url,
+    pub lastmod: MyDateTime,
+}
+
+/// A sitemap listing `urls`
+pub fn urlset(urls: &[SitemapUrl]) -> String {
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+    ));
+    for url in urls {
+        xml.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+            escape(url.loc.as_str()),
+            url.lastmod.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ));
+    }
+    xml.push_str("</urlset>");
+    xml
+}
+
+/// A sitemap index, pointing at each page of a sitemap
+pub fn index(pages: &[Url], lastmod: &MyDateTime) -> String {
+    let lastmod = lastmod.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+    ));
+    for page in pages {
+        xml.push_str(&format!(
+            "<sitemap><loc>{}</loc><lastmod>{lastmod}</lastmod></sitemap>",
+            escape(page.as_str()),
+        ));
+    }
+    xml.push_str("</sitemapindex>");
+    xml
+}
@@ -0,0 +1,236 @@
+//! Where the blog's markdown is read from.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    pin::Pin,
+    time::{Duration, SystemTime},
+};
+
+use log::{debug, error, warn};
+use tokio::{fs::{self, File}, io::AsyncReadExt};
+
+/// Id under which a [`ContentSource`] provides the index file
+pub const INDEX_ID: &str = "/index";
+
+pub type SourceFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, io::Error>> + Send + 'a>>;
+
+/// Provides the markdown of posts and the index to [`PostDb`](super::db::PostDb).
+pub trait ContentSource: Send + Sync {
+    /// Reads the markdown of the post with `id`, or of the index for
+    /// [`INDEX_ID`], along with when it was last modified. Fails with
+    /// [`ErrorKind::NotFound`] if there is no such post.
+    fn read<'a>(&'a self, id: &'a str) -> SourceFuture<'a, (Vec<u8>, SystemTime)>;
+
+    /// When the post with `id` was last modified, so that it is only read
+    /// again if it changed
+    fn modified<'a>(&'a self, id: &'a str) -> SourceFuture<'a, SystemTime> {
+        Box::pin(async move { Ok(self.read(id).await?.1) })
+    }
+
+    /// Ids of every post, not including the index
+    fn list(&self) -> SourceFuture<'_, Vec<String>>;
+
+    /// Whether there is a post with `id`
+    fn exists<'a>(&'a self, id: &'a str) -> SourceFuture<'a, bool> {
+        Box::pin(async move { Ok(self.read(id).await.is_ok()) })
+    }
+}
+
+/// Reads posts from `{id}.md` files in a directory
+pub struct FsContentSource {
+    posts_dir: PathBuf,
+    index_path: PathBuf,
+}
+
+impl FsContentSource {
+    pub fn new(posts_dir: PathBuf, index_path: PathBuf) -> Result<Self, io::Error> {
+        let index_path = dunce::canonicalize(&index_path).map_err(|err| {
+            io::Error::new(err.kind(), format!("Index file {index_path:?}: {err}"))
+        })?;
+
+        if !index_path.is_file() || index_path.extension().is_none_or(|ext| ext != "md") {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Index file {index_path:?} is not a markdown file"),
+            ));
+        }
+
+        Ok(Self {
+            posts_dir: dunce::canonicalize(posts_dir)?,
+            index_path,
+        })
+    }
+
+    fn post_path(&self, id: &str) -> Result<PathBuf, io::Error> {
+        fn invalid_path(id: &str) -> io::Error {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid post id {id:?}")
+            )
+        }
+
+        // Not with_extension, which would replace the language of a translation.
+        // Other than a name too long to be a file, failures here are the
+        // server's, like a lack of permission, and not the id's fault.
+        let path = dunce::canonicalize(self.posts_dir.join(format!("{id}.md")))
+            .map_err(|err| match err.kind() {
+                ErrorKind::InvalidFilename => invalid_path(id),
+                _ => err,
+            })?;
+
+        let valid_filename =
+            path.extension().map_or(false, |ext| ext == "md") &&
+            path.file_name().map_or(false, |name| {
+                name.to_str().map_or(false, |name| name.starts_with(|c| matches!(c, 'A'..='Z' | 'a'..='z' | '0'..='9')))
+            }
+        );
+
+        let valid_parent_path = path.starts_with(&self.posts_dir);
+
+        if !valid_parent_path {
+            warn!("Suspicious post id={id:?} did not start with canonical posts_dir");
+            Err(invalid_path(id))
+        } else if !valid_filename {
+            Err(invalid_path(id))
+        } else {
+            Ok(path)
+        }
+    }
+
+    /// Finds and opens the file for `id`, without retrying
+    async fn open(&self, id: &str) -> Result<File, io::Error> {
+        let path = if id == INDEX_ID {
+            self.index_path.clone()
+        } else {
+            self.post_path(id)?
+        };
+
+        File::open(&path).await
+    }
+}
+
+impl ContentSource for FsContentSource {
+    fn read<'a>(&'a self, id: &'a str) -> SourceFuture<'a, (Vec<u8>, SystemTime)> {
+        Box::pin(async move {
+            let mut file = open_retrying(self, id).await?;
+
+            let modified = file.metadata().await?.modified()?;
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).await?;
+
+            Ok((contents, modified))
+        })
+    }
+
+    fn modified<'a>(&'a self, id: &'a str) -> SourceFuture<'a, SystemTime> {
+        Box::pin(async move {
+            let file = open_retrying(self, id).await?;
+            file.metadata().await?.modified()
+        })
+    }
+
+    fn list(&self) -> SourceFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            let mut ids = Vec::new();
+            let mut posts_dir_iter = fs::read_dir(&self.posts_dir).await?;
+            while let Some(ent) = posts_dir_iter.next_entry().await? {
+                let path = PathBuf::from(ent.file_name());
+
+                let is_markdown = path.extension().map_or(false, |ext| ext == "md");
+                let is_dotted = path
+                    .file_name()
+                    .map_or(false, |name| name.to_string_lossy().starts_with('.'));
+
+                if is_dotted || !is_markdown {
+                    continue;
+                }
+
+                if let Some(id) = path.with_extension("").file_name().and_then(|s| s.to_str()) {
+                    ids.push(id.to_string());
+                } else {
+                    debug!("not valid");
+                }
+            }
+            Ok(ids)
+        })
+    }
+
+    fn exists<'a>(&'a self, id: &'a str) -> SourceFuture<'a, bool> {
+        Box::pin(async move {
+            Ok(fs::metadata(self.posts_dir.join(format!("{id}.md")))
+                .await
+                .is_ok_and(|meta| meta.is_file()))
+        })
+    }
+}
+
+/// Posts held in memory, keyed by id, with the index under [`INDEX_ID`]
+pub struct MemoryContentSource {
+    posts: HashMap<String, (String, SystemTime)>,
+}
+
+impl MemoryContentSource {
+    pub fn new(posts: HashMap<String, (String, SystemTime)>) -> Self {
+        Self { posts }
+    }
+}
+
+impl ContentSource for MemoryContentSource {
+    fn read<'a>(&'a self, id: &'a str) -> SourceFuture<'a, (Vec<u8>, SystemTime)> {
+        Box::pin(async move {
+            let (markdown, modified) = self.posts.get(id).ok_or_else(|| {
+                io::Error::new(ErrorKind::NotFound, format!("No post with id {id:?}"))
+            })?;
+            Ok((markdown.clone().into_bytes(), *modified))
+        })
+    }
+
+    fn modified<'a>(&'a self, id: &'a str) -> SourceFuture<'a, SystemTime> {
+        Box::pin(async move {
+            self.posts.get(id).map(|(_, modified)| *modified).ok_or_else(|| {
+                io::Error::new(ErrorKind::NotFound, format!("No post with id {id:?}"))
+            })
+        })
+    }
+
+    fn list(&self) -> SourceFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            Ok(self.posts.keys().filter(|id| *id != INDEX_ID).cloned().collect())
+        })
+    }
+}
+
+/// Attempts at opening a post before giving up on errors that may go away
+/// by themselves, like on a network filesystem
+const OPEN_ATTEMPTS: u32 = 3;
+
+/// Retries finding the file as well as opening it, since resolving its path
+/// can fail the same ways, e.g. while an editor saves over it
+async fn open_retrying(source: &FsContentSource, id: &str) -> Result<File, io::Error> {
+    let mut delay = Duration::from_millis(50);
+    let mut attempt = 1;
+    loop {
+        match source.open(id).await {
+            Err(err) if is_transient(&err) && attempt < OPEN_ATTEMPTS => {
+                warn!("Opening post {id:?} failed, retrying in {delay:?}: {err}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => {
+                return result.inspect_err(|err| {
+                    if !matches!(err.kind(), ErrorKind::NotFound | ErrorKind::InvalidInput) {
+                        error!("{err} (opening post {id:?})");
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
@@ -0,0 +1,95 @@
+//! Helpers for turning markup into short plain-text snippets.
+
+/// Removes HTML tags from `html`, decodes entities, and collapses whitespace.
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = html_escape::decode_html_entities(&text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Truncates `text` to at most `max_len` characters, cutting on a word
+/// boundary and appending an ellipsis if anything was removed.
+pub fn truncate_words(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_string();
+    }
+
+    // Leave room for the ellipsis
+    let limit = text
+        .char_indices()
+        .nth(max_len.saturating_sub(1))
+        .map_or(text.len(), |(i, _)| i);
+
+    let truncated = &text[..limit];
+    let truncated = if text[limit..].starts_with(char::is_whitespace) {
+        truncated
+    } else {
+        // Drop the partial word at the end
+        truncated.rfind(char::is_whitespace).map_or(truncated, |i| &truncated[..i])
+    };
+
+    let truncated = truncated.trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation());
+    format!("{truncated}…")
+}
+
+/// The first sentence of `text`, truncated like [`truncate_words`] if it is
+/// longer than `max_len` characters.
+pub fn first_sentence(text: &str, max_len: usize) -> String {
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| {
+            matches!(c, '.' | '!' | '?')
+                && text[i + c.len_utf8()..].starts_with(char::is_whitespace)
+        })
+        .map_or(text.len(), |(i, c)| i + c.len_utf8());
+
+    truncate_words(&text[..end], max_len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{first_sentence, strip_html, truncate_words};
+
+    #[test]
+    fn strip_tags_and_entities() {
+        let input = "<p>Fish &amp; <em>chips</em></p>\n<p>are good</p>";
+        assert_eq!(strip_html(input), "Fish & chips are good");
+    }
+
+    #[test]
+    fn truncate_short_text() {
+        assert_eq!(truncate_words("hello world", 11), "hello world");
+    }
+
+    #[test]
+    fn truncate_on_word_boundary() {
+        assert_eq!(truncate_words("hello wonderful world", 12), "hello…");
+        assert_eq!(truncate_words("hello wonderful world", 16), "hello wonderful…");
+    }
+
+    #[test]
+    fn truncate_one_long_word() {
+        assert_eq!(truncate_words("abcdefghij", 5), "abcd…");
+    }
+
+    #[test]
+    fn first_sentence_only() {
+        assert_eq!(first_sentence("Version 1.2 is out! More soon.", 160), "Version 1.2 is out!");
+        assert_eq!(first_sentence("No ending", 160), "No ending");
+        assert_eq!(first_sentence("A very long sentence. Short.", 10), "A very…");
+    }
+}
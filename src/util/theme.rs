@@ -0,0 +1,75 @@
+//! Assets that can be overridden with `--theme-dir`.
+
+use std::{fs, io::ErrorKind, path::Path};
+
+use log::info;
+
+const RSS_ICON: &str = include_str!("../res/rss-icon.svg");
+const COFFEE_ICON: &str = include_str!("../res/coffee.svg");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub rss_icon: String,
+    pub coffee_icon: String,
+    /// Replaces `public/styles.css` when set.
+    pub styles: Option<String>,
+    /// `favicon.svg` as a data URI, inlined into every page instead of
+    /// linking to `public/favicon.png`.
+    pub favicon: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            rss_icon: RSS_ICON.to_string(),
+            coffee_icon: COFFEE_ICON.to_string(),
+            styles: None,
+            favicon: None,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads the theme from `dir`. Any file missing from `dir` falls back
+    /// to the asset compiled into ustack.
+    pub fn load(dir: &Path) -> Result<Self, std::io::Error> {
+        let defaults = Self::default();
+
+        Ok(Self {
+            rss_icon: read_override(dir, "rss-icon.svg")?.unwrap_or(defaults.rss_icon),
+            coffee_icon: read_override(dir, "coffee.svg")?.unwrap_or(defaults.coffee_icon),
+            styles: read_override(dir, "styles.css")?,
+            favicon: read_override(dir, "favicon.svg")?.map(|svg| svg_data_uri(&svg)),
+        })
+    }
+}
+
+fn svg_data_uri(svg: &str) -> String {
+    let svg = percent_encoding::utf8_percent_encode(svg.trim(), percent_encoding::NON_ALPHANUMERIC);
+    format!("data:image/svg+xml,{svg}")
+}
+
+fn read_override(dir: &Path, name: &str) -> Result<Option<String>, std::io::Error> {
+    let path = dir.join(name);
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            info!("Using theme override {path:?}");
+            Ok(Some(text))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::svg_data_uri;
+
+    #[test]
+    fn encode_svg_data_uri() {
+        assert_eq!(
+            svg_data_uri("<svg fill=\"#000\"/>\n"),
+            "data:image/svg+xml,%3Csvg%20fill%3D%22%23000%22%2F%3E"
+        );
+    }
+}
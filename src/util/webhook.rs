@@ -0,0 +1,69 @@
+//! Outgoing notifications when posts change.
+
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local};
+use hyper::{client::HttpConnector, header::CONTENT_TYPE, Body, Client, Method, Request};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use log::{debug, warn};
+use url::Url;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Webhook {
+    url: Url,
+    client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl Webhook {
+    pub fn new(url: Url) -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Self {
+            url,
+            client: Client::builder().build(connector),
+        }
+    }
+
+    /// Tells the webhook that a post was modified. This does not wait for a
+    /// response; failures are logged.
+    pub fn notify(&self, id: &str, modified: SystemTime) {
+        let payload = serde_json::json!({
+            "id": id,
+            "modified": DateTime::<Local>::from(modified).to_rfc3339(),
+        });
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.as_str())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(payload.to_string()));
+
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                warn!("Could not build webhook request for {id}: {err}");
+                return;
+            }
+        };
+
+        let client = self.client.clone();
+        let id = id.to_string();
+        tokio::spawn(async move {
+            match tokio::time::timeout(TIMEOUT, client.request(request)).await {
+                Ok(Ok(response)) if response.status().is_success() => {
+                    debug!("Webhook notified of change to {id}");
+                }
+                Ok(Ok(response)) => {
+                    warn!("Webhook for {id} responded with {}", response.status());
+                }
+                Ok(Err(err)) => warn!("Webhook for {id} failed: {err}"),
+                Err(_) => warn!("Webhook for {id} timed out"),
+            }
+        });
+    }
+}
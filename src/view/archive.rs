@@ -1,49 +1,129 @@
+use std::sync::Arc;
+
 use dioxus::prelude::*;
 use url::Url;
 
-use crate::{model::Metadata, util::db::PostMeta};
-use super::header;
+use crate::{model::{HeaderLabels, Metadata}, util::{db::PostMeta, theme::Theme}};
+use super::{header, post_list};
 
-pub struct ArchiveProps {
+/// Posts listed under a heading, like the year they were published in
+pub struct ArchiveGroup {
+    pub heading: Option<String>,
     pub posts: Vec<PostMeta>,
+}
+
+pub struct ArchiveProps {
+    /// The page's posts, in a single group without a heading unless the
+    /// archive is grouped
+    pub groups: Vec<ArchiveGroup>,
     pub canonical_url: Url,
     pub coffee_link: Option<Url>,
+    pub site_title: String,
     pub site_title_short: String,
-    pub metadata: Metadata
+    pub theme: Arc<Theme>,
+    pub header_labels: HeaderLabels,
+    pub styles_href: String,
+    pub theme_color: Option<String>,
+    pub color_scheme: Option<String>,
+    pub opensearch: bool,
+    pub metadata: Metadata,
+    /// URL of the previous page, if there is one
+    pub prev_url: Option<Url>,
+    /// URL of the next page, if there is one
+    pub next_url: Option<Url>,
+    /// Whether the posts are in some order other than newest first
+    pub sorted: bool,
+    pub fragment: bool,
 }
 
 pub fn archive(cx: Scope<ArchiveProps>) -> Element {
+    let (prev_text, next_text) = if cx.props.sorted {
+        ("Previous Page", "Next Page")
+    } else {
+        ("Newer Posts", "Older Posts")
+    };
+
+    let page_link = |url: Option<&Url>, text: &str| match url {
+        Some(url) => {
+            let href = match url.query() {
+                Some(query) => format!("{}?{query}", url.path()),
+                None => url.path().to_string(),
+            };
+            cx.render(rsx! {
+                a { href: "{href}", "{text}" }
+            })
+        }
+        None => cx.render(rsx! {
+            a { class: "disabled", "{text}" }
+        }),
+    };
+
+    let newer = page_link(cx.props.prev_url.as_ref(), prev_text);
+    let older = page_link(cx.props.next_url.as_ref(), next_text);
+
+    let groups = cx.props.groups.iter().map(|group| match &group.heading {
+        Some(heading) => rsx! {
+            section {
+                h2 { "{heading}" }
+                post_list::post_list {
+                    posts: &group.posts
+                }
+            }
+        },
+        None => rsx! {
+            post_list::post_list {
+                posts: &group.posts
+            }
+        },
+    });
+
+    let content = rsx! {
+        header::site_header {
+            site_title: &cx.props.site_title,
+            site_title_short: &cx.props.site_title_short,
+            coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str()),
+            theme: &cx.props.theme,
+            labels: &cx.props.header_labels,
+        }
+
+        section {
+            id: "content",
+            groups
+        }
+
+        nav {
+            class: "pages",
+            aria_label: "Pages",
+            newer
+            older
+        }
+    };
+
+    if cx.props.fragment {
+        return cx.render(content);
+    }
+
     cx.render(rsx! {
         super::preamble {
             title: "Archive",
+            site_title: &cx.props.site_title,
             highlight: false,
             author: cx.props.metadata.author.as_deref(),
             summary: None,
             url: &cx.props.canonical_url,
+            styles_href: &cx.props.styles_href,
+            favicon: cx.props.theme.favicon.as_deref(),
+            theme_color: cx.props.theme_color.as_deref(),
+            color_scheme: cx.props.color_scheme.as_deref(),
+            opensearch: cx.props.opensearch,
+            prev: cx.props.prev_url.as_ref(),
+            next: cx.props.next_url.as_ref(),
         }
 
         body {
             main {
                 class: "archive",
-                header::site_header {
-                    site_title: &cx.props.metadata.title,
-                    site_title_short: &cx.props.site_title_short,
-                    coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str())
-                }
-
-                section {
-                    ol {
-                        for post in cx.props.posts.iter() {
-                            li {
-                                a {
-                                    href: "/p/{post.id}",
-                                    h3 { "{post.title}" }
-                                }
-                                post.summary.as_deref().unwrap_or_else(|| "")
-                            }
-                        }
-                    }
-                }
+                content
             }
         }
     })
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use dioxus::prelude::*;
+use url::Url;
+
+use crate::{model::HeaderLabels, util::theme::Theme};
+use super::header;
+
+/// The site's header and styles, which the error page only has if the
+/// index is loaded
+pub struct ErrorPageSite {
+    pub site_title: String,
+    pub site_title_short: String,
+    pub site_url: Url,
+    pub coffee_link: Option<Url>,
+    pub theme: Arc<Theme>,
+    pub header_labels: HeaderLabels,
+    pub styles_href: String,
+    pub theme_color: Option<String>,
+    pub color_scheme: Option<String>,
+    pub opensearch: bool,
+}
+
+/// Shown instead of the details of an internal error, which are only logged
+pub struct ErrorPageProps {
+    pub site: Option<ErrorPageSite>,
+}
+
+pub fn error_page(cx: Scope<ErrorPageProps>) -> Element {
+    let message = rsx! {
+        h1 { "500: Internal Server Error" }
+        p { "Something went wrong while loading this page. Please try again later." }
+    };
+
+    let Some(site) = &cx.props.site else {
+        return cx.render(rsx! {
+            body {
+                main { message }
+            }
+        });
+    };
+
+    cx.render(rsx! {
+        super::preamble {
+            title: "Error",
+            site_title: &site.site_title,
+            highlight: false,
+            author: None,
+            summary: None,
+            url: &site.site_url,
+            styles_href: &site.styles_href,
+            favicon: site.theme.favicon.as_deref(),
+            theme_color: site.theme_color.as_deref(),
+            color_scheme: site.color_scheme.as_deref(),
+            opensearch: site.opensearch,
+        }
+
+        body {
+            main {
+                class: "error",
+                header::site_header {
+                    site_title: &site.site_title,
+                    site_title_short: &site.site_title_short,
+                    coffee_link: site.coffee_link.as_ref().map(|c| c.as_str()),
+                    theme: &site.theme,
+                    labels: &site.header_labels,
+                }
+                section {
+                    id: "content",
+                    message
+                }
+            }
+        }
+    })
+}
@@ -1,11 +1,15 @@
 use dioxus::prelude::*;
 
+use crate::{model::HeaderLabels, util::theme::Theme};
+
 #[derive(Props)]
 pub struct HeaderProps<'a> {
     pub site_title: &'a str,
     pub site_title_short: &'a str,
     #[props(!optional)]
-    pub coffee_link: Option<&'a str>
+    pub coffee_link: Option<&'a str>,
+    pub theme: &'a Theme,
+    pub labels: &'a HeaderLabels,
 }
 
 pub fn site_header<'a>(cx: Scope<'a, HeaderProps<'a>>) -> Element<'a> {
@@ -13,12 +17,21 @@ pub fn site_header<'a>(cx: Scope<'a, HeaderProps<'a>>) -> Element<'a> {
         .map(|c| cx.render(rsx! {
             a {
                 href: "{c}",
-                dangerous_inner_html: include_str!("../res/coffee.svg")
+                aria_label: "{cx.props.labels.coffee}",
+                span {
+                    aria_hidden: "true",
+                    dangerous_inner_html: "{cx.props.theme.coffee_icon}"
+                }
             }
         }));
 
     cx.render(rsx! {
         header {
+            a {
+                class: "skip-link",
+                href: "#content",
+                "Skip to content"
+            }
             a {
                 href: "/",
                 h1 {
@@ -32,9 +45,14 @@ pub fn site_header<'a>(cx: Scope<'a, HeaderProps<'a>>) -> Element<'a> {
             }
 
             nav {
+                aria_label: "Feeds",
                 a {
                     href: "/rss",
-                    dangerous_inner_html: include_str!("../res/rss-icon.svg")
+                    aria_label: "{cx.props.labels.rss}",
+                    span {
+                        aria_hidden: "true",
+                        dangerous_inner_html: "{cx.props.theme.rss_icon}"
+                    }
                 }
                 coffee
             }
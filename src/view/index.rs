@@ -1,65 +1,121 @@
+use std::sync::Arc;
+
 use dioxus::prelude::*;
 use url::Url;
 
-use crate::util::db::{PostMeta, PostContent};
-use super::header;
+use crate::{model::{HeaderLabels, IndexLayout, NavLink}, util::{db::{PostMeta, PostContent}, theme::Theme}};
+use super::{header, post_list};
 
 #[derive(Props, PartialEq)]
 pub struct IndexProps {
     pub posts: Vec<PostMeta>,
+    pub featured: Vec<PostMeta>,
     pub content: PostContent,
     pub canonical_url: Url,
     #[props(!optional)]
     pub coffee_link: Option<Url>,
+    pub site_title: String,
     pub site_title_short: String,
+    pub theme: Arc<Theme>,
+    pub header_labels: HeaderLabels,
+    pub styles_href: String,
+    pub theme_color: Option<String>,
+    pub color_scheme: Option<String>,
+    pub opensearch: bool,
+    pub layout: IndexLayout,
+    /// Links below the header, e.g. to the archive
+    pub nav: Vec<NavLink>,
+    pub fragment: bool,
 }
 
 pub fn index(cx: Scope<IndexProps>) -> Element {
+    let intro = cx.render(rsx! {
+        div {
+            class: "index-content",
+            dangerous_inner_html: "{cx.props.content.body}"
+        }
+    });
+
+    let featured = if cx.props.featured.is_empty() {
+        None
+    } else {
+        cx.render(rsx! {
+            section {
+                class: "featured",
+                h2 { "Featured" }
+                post_list::post_list {
+                    posts: &cx.props.featured
+                }
+            }
+        })
+    };
+
+    let list = cx.render(rsx! {
+        featured
+        section {
+            h2 { "Recent Posts" }
+            post_list::post_list {
+                posts: &cx.props.posts
+            }
+        }
+    });
+
+    let nav = cx.props.nav.iter().map(|link| rsx! {
+        a {
+            href: "{link.href}",
+            "{link.label}"
+        }
+    });
+
+    let (first, second) = match cx.props.layout {
+        IndexLayout::IntroFirst => (intro, list),
+        IndexLayout::ListFirst => (list, intro),
+        IndexLayout::ListOnly => (list, None),
+        IndexLayout::IntroOnly => (intro, None),
+    };
+
+    let content = rsx! {
+        header::site_header {
+            site_title: &cx.props.site_title,
+            site_title_short: &cx.props.site_title_short,
+            coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str()),
+            theme: &cx.props.theme,
+            labels: &cx.props.header_labels,
+        }
+        nav {
+            aria_label: "Site",
+            nav
+        }
+        div {
+            id: "content",
+            first
+            second
+        }
+    };
+
+    if cx.props.fragment {
+        return cx.render(content);
+    }
+
     cx.render(rsx! {
         super::preamble {
             title: &cx.props.content.metadata.title,
+            site_title: &cx.props.site_title,
             highlight: cx.props.content.metadata.highlight,
             author: cx.props.content.metadata.author.as_deref(),
             summary: cx.props.content.metadata.summary.as_deref(),
             tags: &cx.props.content.metadata.tags,
             url: &cx.props.canonical_url,
+            styles_href: &cx.props.styles_href,
+            favicon: cx.props.theme.favicon.as_deref(),
+            theme_color: cx.props.theme_color.as_deref(),
+            color_scheme: cx.props.color_scheme.as_deref(),
+            opensearch: cx.props.opensearch,
         }
         body {
             main {
                 class: "index",
-                header::site_header {
-                    site_title: &cx.props.content.metadata.title,
-                    site_title_short: &cx.props.site_title_short,
-                    coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str())
-                }
-                nav {
-                    a {
-                        href: "/archive",
-                        "Archive"
-                    },
-                    a {
-                        href: "/random",
-                        "Random Post"
-                    }
-                }
-                div {
-                    class: "index-content",
-                    dangerous_inner_html: "{cx.props.content.body}"
-                }
-                section {
-                    h2 { "Recent Posts" }
-                    ol {
-                        for post in cx.props.posts.iter() {
-                            li {
-                                a {
-                                    href: "/p/{post.id}",
-                                    h3 { "{post.title}" }
-                                }
-                                post.summary.as_deref().unwrap_or_else(|| "")
-                            }
-                        }
-                    }
-                }
+                content
             }
         }
     })
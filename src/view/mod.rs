@@ -4,6 +4,10 @@ mod index;
 mod preamble;
 mod archive;
 mod header;
+mod post_list;
+mod series;
+mod subscribe;
+mod error_page;
 
 pub mod social;
 
@@ -12,3 +16,158 @@ pub use index::*;
 pub use post::*;
 pub use preamble::*;
 pub use archive::*;
+pub use series::*;
+pub use subscribe::*;
+pub use error_page::*;
+
+use std::{io, sync::Arc};
+
+use dioxus::prelude::VirtualDom;
+
+use crate::{model::IndexMetadata, util::{self, db::PostContent, theme::Theme}};
+
+/// Renders a post as a complete page of `site`, from its markdown alone,
+/// without a server or any files. `id` places the page at `/p/{id}` on the
+/// site. The page has no links to other posts, since there are none to
+/// link to.
+pub fn render_post_html(id: &str, markdown: &str, site: &IndexMetadata) -> Result<String, io::Error> {
+    let post = PostContent::from_markdown(id, markdown, site)?;
+    let comments_script = site.comments.clone().filter(|_| post.metadata.comments);
+    let coffee_link = post.metadata.coffee_link(site.coffee.as_ref()).cloned();
+    let mut canonical_url = site.url.clone();
+    canonical_url.path_segments_mut()
+        .expect("site_url shall be a base")
+        .pop_if_empty()
+        .extend(&["p", id]);
+
+    let vdom = VirtualDom::new_with_props(
+        post::post,
+        PostProps {
+            post,
+            site_title: site.title.clone(),
+            site_title_short: site.short_title.clone().unwrap_or_else(|| site.title.clone()),
+            theme: Arc::new(Theme::default()),
+            header_labels: site.header_labels.clone(),
+            styles_href: "/public/styles.css".to_string(),
+            theme_color: site.theme_color.clone(),
+            color_scheme: site.color_scheme.clone(),
+            opensearch: site.opensearch,
+            canonical_url,
+            twitter_link: None,
+            coffee_link,
+            edit_link: None,
+            comments_script,
+            series: vec![],
+            languages: vec![],
+            read_next: None,
+            recently_updated: false,
+            stats: None,
+            fragment: false,
+        },
+    );
+    Ok(util::render_html(vdom, &site.lang))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::SystemTime};
+
+    use dioxus::prelude::VirtualDom;
+    use url::Url;
+
+    use crate::{model::{HeaderLabels, IndexLayout, Metadata}, util::{self, db::PostContent, theme::Theme}};
+    use super::{index, post, render_post_html, IndexProps, PostProps};
+
+    fn content(id: &str, title: &str) -> PostContent {
+        PostContent {
+            id: id.to_string(),
+            body: String::new(),
+            last_modified: SystemTime::UNIX_EPOCH,
+            metadata: Metadata::from_yaml(format!("title: {title}"), true).unwrap(),
+            source: None,
+        }
+    }
+
+    fn site_header(html: &str) -> &str {
+        let start = html.find("<header>").unwrap();
+        let end = html[start..].find("</header>").unwrap();
+        &html[start..start + end]
+    }
+
+    #[test]
+    fn header_matches_on_index_and_post() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let theme = Arc::new(Theme::default());
+
+        let index = VirtualDom::new_with_props(index, IndexProps {
+            posts: vec![],
+            featured: vec![],
+            // The index's own title should not leak into the header
+            content: content("/index", "Welcome"),
+            canonical_url: url.clone(),
+            coffee_link: None,
+            site_title: "My Blog".to_string(),
+            site_title_short: "Blog".to_string(),
+            layout: IndexLayout::default(),
+            nav: vec![],
+            theme: theme.clone(),
+            header_labels: HeaderLabels::default(),
+            styles_href: "/public/styles.css".to_string(),
+            theme_color: None,
+            color_scheme: None,
+            opensearch: false,
+            fragment: false,
+        });
+
+        let post = VirtualDom::new_with_props(post, PostProps {
+            post: content("hello", "Hello"),
+            site_title: "My Blog".to_string(),
+            site_title_short: "Blog".to_string(),
+            canonical_url: url,
+            twitter_link: None,
+            coffee_link: None,
+            edit_link: None,
+            comments_script: None,
+            series: vec![],
+            languages: vec![],
+            read_next: None,
+            recently_updated: false,
+            stats: None,
+            theme,
+            header_labels: HeaderLabels::default(),
+            styles_href: "/public/styles.css".to_string(),
+            theme_color: None,
+            color_scheme: None,
+            opensearch: false,
+            fragment: false,
+        });
+
+        let index = util::render_html(index, "en");
+        let post = util::render_html(post, "en");
+
+        assert!(site_header(&index).contains("My Blog"));
+        assert_eq!(site_header(&index), site_header(&post));
+
+        // The skip link comes first, and has somewhere to go
+        for html in [&index, &post] {
+            assert!(site_header(html).starts_with(r##"<header><a class="skip-link" href="#content">"##));
+            assert!(html.contains(r#"id="content""#));
+            // The icons are named for screen readers
+            assert!(site_header(html).contains(r#"<a href="/rss" aria-label="RSS feed"><span aria-hidden="true">"#));
+        }
+    }
+
+    #[test]
+    fn render_post_without_server() {
+        let site = crate::model::IndexMetadata::from_yaml("title: My Blog\nurl: https://example.com", true).unwrap();
+        let html = render_post_html("hello", "---\ntitle: Hello\ncreated: 2 Jan 2020 15:04 +0100\n---\nHello, *world*!\n", &site).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Hello</h1>"));
+        assert!(html.contains("<em>world</em>"));
+        assert!(html.contains(r#"datetime="2020-01-02T15:04:00+01:00""#));
+        assert!(site_header(&html).contains("My Blog"));
+        assert!(html.contains(r#"<link rel="canonical" href="https://example.com/p/hello"/>"#));
+        assert!(render_post_html("hello", "no front matter", &site).is_err());
+    }
+}
@@ -1,25 +1,60 @@
+use std::{borrow::Cow, sync::Arc};
+
+use chrono::{DateTime, FixedOffset};
 use dioxus::prelude::*;
 use url::Url;
 
-use super::{social, header};
-use crate::util::db::PostContent;
+use super::{social, header, LanguageAlternate};
+use crate::{model::HeaderLabels, util::{self, db::{PostContent, PostMeta}, theme::Theme}};
+
+/// How many posts the site has, and when the newest was published
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteStats {
+    pub post_count: usize,
+    pub latest: Option<DateTime<FixedOffset>>,
+}
 
 #[derive(Props, PartialEq)]
 pub struct PostProps {
     pub post: PostContent,
     pub site_title: String,
     pub site_title_short: String,
+    pub theme: Arc<Theme>,
+    pub header_labels: HeaderLabels,
+    pub styles_href: String,
+    pub theme_color: Option<String>,
+    pub color_scheme: Option<String>,
+    pub opensearch: bool,
     pub canonical_url: Url,
     #[props(!optional)]
     pub twitter_link: Option<Url>,
     #[props(!optional)]
     pub coffee_link: Option<Url>,
+    /// Where the post can be edited, e.g. on GitHub
+    #[props(!optional)]
+    pub edit_link: Option<Url>,
+    #[props(!optional)]
+    pub comments_script: Option<String>,
+    /// Other posts in the same series, including this one
+    pub series: Vec<PostMeta>,
+    /// Every language the post is available in, if it has translations
+    pub languages: Vec<LanguageAlternate>,
+    /// The newest other post, suggested at the end of the article
+    #[props(!optional)]
+    pub read_next: Option<PostMeta>,
+    /// Whether the post was modified within the site's `recently_updated_days`
+    pub recently_updated: bool,
+    /// Shown in the footer if the site has `footer_stats`
+    #[props(!optional)]
+    pub stats: Option<SiteStats>,
+    pub fragment: bool,
 }
 
 pub fn post(cx: Scope<PostProps>) -> Element {
     let published = cx.props.post.published();
     let timestamp = published.format("%A, %e %B %Y");
-    let datetime = published.format("%F");
+    // With the time and offset, for scripts that show e.g. "3 days ago"
+    let datetime = published.to_rfc3339();
     let time_title = published.format("%e %B %Y");
 
     let address = if let Some(author) = &cx.props.post.metadata.author {
@@ -53,6 +88,13 @@ pub fn post(cx: Scope<PostProps>) -> Element {
         })
     };
 
+    let badge = cx.props.recently_updated.then(|| rsx! {
+        span {
+            class: "badge",
+            "Recently updated"
+        }
+    });
+
     let twitter = cx
         .props
         .twitter_link
@@ -63,38 +105,195 @@ pub fn post(cx: Scope<PostProps>) -> Element {
             }
         }));
 
+    let edit = cx
+        .props
+        .edit_link
+        .as_ref()
+        .map(|link| cx.render(rsx! {
+            a {
+                class: "edit-link",
+                href: "{link}",
+                "Edit this page"
+            }
+        }));
+
+    let stats = cx
+        .props
+        .stats
+        .as_ref()
+        .map(|stats| {
+            let posts = if stats.post_count == 1 { "post" } else { "posts" };
+            let latest = stats.latest.map(|latest| {
+                let datetime = latest.to_rfc3339();
+                let date = latest.format("%e %B %Y");
+                rsx! {
+                    ", the latest on "
+                    time {
+                        datetime: "{datetime}",
+                        "{date}"
+                    }
+                }
+            });
+            cx.render(rsx! {
+                p {
+                    class: "site-stats",
+                    "{stats.post_count} {posts}"
+                    latest
+                }
+            })
+        });
+
+    let series = cx
+        .props
+        .post
+        .metadata
+        .series
+        .as_ref()
+        .filter(|_| !cx.props.series.is_empty())
+        .map(|series| {
+            let series_path = util::series_path(series);
+            let part = cx.props.series
+                .iter()
+                .position(|p| p.id == cx.props.post.id)
+                .map_or(0, |i| i + 1);
+            let count = cx.props.series.len();
+
+            cx.render(rsx! {
+                aside {
+                    class: "series",
+                    p {
+                        "Part {part} of {count} in "
+                        a { href: "{series_path}", "{series}" }
+                    }
+                    ol {
+                        for post in cx.props.series.iter() {
+                            li {
+                                if post.id == cx.props.post.id {
+                                    rsx! { strong { "{post.title}" } }
+                                } else {
+                                    rsx! { a { href: "/p/{post.id}", "{post.title}" } }
+                                }
+                            }
+                        }
+                    }
+                }
+            })
+        });
+
+    let read_next = cx
+        .props
+        .read_next
+        .as_ref()
+        .map(|post| cx.render(rsx! {
+            aside {
+                class: "read-next",
+                p { "Read next" }
+                a {
+                    href: "/p/{post.id}",
+                    h3 { "{post.title}" }
+                }
+                post.summary.as_deref().unwrap_or_default()
+            }
+        }));
+
+    let comments = cx
+        .props
+        .comments_script
+        .as_ref()
+        .map(|script| cx.render(rsx! {
+            section {
+                id: "comments",
+                script {
+                    src: "/public/{script}"
+                }
+            }
+        }));
+
+    let banner = cx
+        .props
+        .post
+        .metadata
+        .banner
+        .as_ref()
+        .map(|banner| {
+            let alt = cx.props.post.metadata.banner_alt.as_deref().unwrap_or("");
+            cx.render(rsx! {
+                figure {
+                    class: "banner",
+                    img {
+                        src: "/public/{banner}",
+                        alt: "{alt}"
+                    }
+                }
+            })
+        });
+
+    let content = rsx! {
+        header::site_header {
+            site_title: &cx.props.site_title,
+            site_title_short: &cx.props.site_title_short,
+            coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str()),
+            theme: &cx.props.theme,
+            labels: &cx.props.header_labels,
+        }
+        article {
+            id: "content",
+            banner
+            header {
+                h1 { "{cx.props.post.metadata.title}" },
+                div {
+                    class: "byline",
+                    address,
+                    badge
+                }
+            }
+            series
+            div {
+                class: "article-body",
+                dangerous_inner_html: cx.props.post.body.as_str()
+            }
+            read_next
+        }
+        comments
+    };
+
+    if cx.props.fragment {
+        return cx.render(content);
+    }
+
+    // Generated summaries are owned, so they have to live as long as the scope
+    let summary = cx.props.post.effective_summary().map(|summary| match summary {
+        Cow::Borrowed(summary) => summary,
+        Cow::Owned(summary) => &*cx.bump().alloc_str(&summary),
+    });
+
     cx.render(rsx! {
         super::preamble {
             title: &cx.props.post.metadata.title,
+            site_title: &cx.props.site_title,
             highlight: cx.props.post.metadata.highlight,
+            mermaid: cx.props.post.metadata.mermaid,
             author: cx.props.post.metadata.author.as_deref(),
-            summary: cx.props.post.metadata.summary.as_deref(),
+            summary: summary,
             tags: &cx.props.post.metadata.tags,
             url: &cx.props.canonical_url,
+            styles_href: &cx.props.styles_href,
+            favicon: cx.props.theme.favicon.as_deref(),
+            theme_color: cx.props.theme_color.as_deref(),
+            color_scheme: cx.props.color_scheme.as_deref(),
+            opensearch: cx.props.opensearch,
+            og_type: Some(cx.props.post.metadata.og_type()),
+            image: cx.props.post.metadata.banner.as_deref(),
+            languages: &cx.props.languages,
         }
         body {
             main {
                 class: "post",
-                header::site_header {
-                    site_title: &cx.props.site_title,
-                    site_title_short: &cx.props.site_title_short,
-                    coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str())
-                }
-                article {
-                    header {
-                        h1 { "{cx.props.post.metadata.title}" },
-                        div {
-                            class: "byline",
-                            address,
-                        }
-                    }
-                    div {
-                        class: "article-body",
-                        dangerous_inner_html: cx.props.post.body.as_str()
-                    }
-                }
+                content
             }
             footer {
+                edit
+                stats
                 twitter
                 social::rss {
                     canonical_url: &cx.props.canonical_url
@@ -0,0 +1,59 @@
+use dioxus::prelude::*;
+
+use crate::util::db::PostMeta;
+
+#[derive(Props)]
+pub struct PostListProps<'a> {
+    pub posts: &'a [PostMeta],
+}
+
+pub fn post_list<'a>(cx: Scope<'a, PostListProps<'a>>) -> Element<'a> {
+    // A new blog, rather than an empty list
+    if cx.props.posts.is_empty() {
+        return cx.render(rsx! {
+            p {
+                class: "no-posts",
+                "No posts yet."
+            }
+        });
+    }
+
+    cx.render(rsx! {
+        ol {
+            for post in cx.props.posts.iter() {
+                li {
+                    if let Some(thumbnail) = &post.thumbnail {
+                        rsx! {
+                            img {
+                                class: "thumbnail",
+                                src: "/public/{thumbnail}",
+                                alt: "",
+                                "loading": "lazy"
+                            }
+                        }
+                    }
+                    a {
+                        href: "/p/{post.id}",
+                        h3 { "{post.title}" }
+                    }
+                    if post.recently_updated {
+                        rsx! {
+                            span {
+                                class: "badge",
+                                "Recently updated"
+                            }
+                        }
+                    }
+                    if let Some(summary) = &post.summary {
+                        rsx! {
+                            p {
+                                class: "summary",
+                                "{summary}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -1,18 +1,58 @@
 use dioxus::prelude::*;
 use url::Url;
 
+/// A version of the page in another language
+#[derive(Debug, PartialEq)]
+pub struct LanguageAlternate {
+    /// A language tag, or `x-default`
+    pub lang: String,
+    pub url: Url,
+}
+
 #[derive(Props)]
 pub struct PreambleProps<'a> {
     title: &'a str,
+    site_title: &'a str,
     url: &'a Url,
     highlight: bool,
+    #[props(default)]
+    mermaid: bool,
     #[props(!optional)]
     author: Option<&'a str>,
     #[props(!optional)]
     summary: Option<&'a str>,
     tags: Option<&'a Vec<String>>,
+    /// OpenGraph type of the page, e.g. `article`.
+    #[props(!optional, default)]
+    og_type: Option<&'a str>,
+    /// Image in the `public` directory used when sharing the page.
+    #[props(!optional, default)]
+    image: Option<&'a str>,
+    /// Previous page of a paginated listing.
+    #[props(!optional, default)]
+    prev: Option<&'a Url>,
+    /// Next page of a paginated listing.
+    #[props(!optional, default)]
+    next: Option<&'a Url>,
+    #[props(default)]
+    languages: &'a [LanguageAlternate],
+    styles_href: &'a str,
+    /// Icon to use instead of `public/favicon.png`, e.g. a data URI.
+    #[props(!optional, default)]
+    favicon: Option<&'a str>,
+    #[props(!optional, default)]
+    theme_color: Option<&'a str>,
+    /// e.g. `light dark`
+    #[props(!optional, default)]
+    color_scheme: Option<&'a str>,
+    /// Whether to link to /opensearch.xml
+    #[props(default)]
+    opensearch: bool,
 }
 
+const MERMAID_INIT: &str = "import mermaid from 'https://unpkg.com/mermaid@10.6.1/dist/mermaid.esm.min.mjs';\
+    mermaid.initialize({ startOnLoad: true });";
+
 pub fn preamble<'a>(cx: Scope<'a, PreambleProps<'a>>) -> Element<'a> {
     let highlight = if cx.props.highlight {
         cx.render(rsx! {
@@ -31,6 +71,17 @@ pub fn preamble<'a>(cx: Scope<'a, PreambleProps<'a>>) -> Element<'a> {
         None
     };
 
+    let mermaid = if cx.props.mermaid {
+        cx.render(rsx! {
+            script {
+                r#type: "module",
+                dangerous_inner_html: MERMAID_INIT
+            }
+        })
+    } else {
+        None
+    };
+
     let author = cx.props.author.and_then(|author| cx.render(rsx! {
         meta { name: "author", content: "{author}" }
     }));
@@ -39,6 +90,59 @@ pub fn preamble<'a>(cx: Scope<'a, PreambleProps<'a>>) -> Element<'a> {
         meta { name: "description", content: "{summary}" }
     }));
 
+    let theme_color = cx.props.theme_color.and_then(|color| cx.render(rsx! {
+        meta { name: "theme-color", content: "{color}" }
+    }));
+
+    let color_scheme = cx.props.color_scheme.and_then(|scheme| cx.render(rsx! {
+        meta { name: "color-scheme", content: "{scheme}" }
+    }));
+
+    let opensearch = cx.props.opensearch.then(|| cx.render(rsx! {
+        link {
+            rel: "search",
+            r#type: "application/opensearchdescription+xml",
+            href: "/opensearch.xml"
+        }
+    })).flatten();
+
+    let og_type = cx.props.og_type.and_then(|og_type| cx.render(rsx! {
+        meta { "property": "og:type", content: "{og_type}" }
+    }));
+
+    let image = cx.props.image
+        .and_then(|image| cx.props.url.join(&format!("/public/{image}")).ok())
+        .and_then(|image| cx.render(rsx! {
+            meta { "property": "og:image", content: "{image}" }
+        }));
+
+    let rss = cx.props.url.join("/rss").ok().and_then(|rss| cx.render(rsx! {
+        link {
+            rel: "alternate",
+            r#type: "application/rss+xml",
+            title: "{cx.props.site_title}",
+            href: "{rss}"
+        }
+    }));
+
+    let prev = cx.props.prev.and_then(|prev| cx.render(rsx! {
+        link { rel: "prev", href: "{prev}" }
+    }));
+
+    let next = cx.props.next.and_then(|next| cx.render(rsx! {
+        link { rel: "next", href: "{next}" }
+    }));
+
+    let favicon = cx.props.favicon.unwrap_or("/public/favicon.png");
+
+    let languages = cx.props.languages.iter().map(|alternate| rsx! {
+        link {
+            rel: "alternate",
+            hreflang: "{alternate.lang}",
+            href: "{alternate.url}"
+        }
+    });
+
     let keywords = if cx.props.tags.map_or(0, |t| t.len()) > 0 {
         let keywords = cx.props.tags.unwrap().join(", ");
         cx.render(rsx! {
@@ -55,15 +159,26 @@ pub fn preamble<'a>(cx: Scope<'a, PreambleProps<'a>>) -> Element<'a> {
             title { "{cx.props.title}" }
             meta { name: "twitter:card", content: "summary" }
             link { rel: "canonical", href: "{cx.props.url}" }
-            link { rel: "icon", href: "/public/favicon.png" }
+            prev
+            next
+            languages
+            link { rel: "icon", href: "{favicon}" }
             link { rel: "apple-touch-icon", href: "/public/favicon.png" }
+            rss
+            opensearch
+            link { rel: "manifest", href: "/manifest.webmanifest" }
             author
             summary
             keywords
+            theme_color
+            color_scheme
+            og_type
+            image
             highlight
+            mermaid
             link {
                 rel: "stylesheet",
-                href: "/public/styles.css"
+                href: "{cx.props.styles_href}"
             }
         }
     })
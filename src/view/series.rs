@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use dioxus::prelude::*;
+use url::Url;
+
+use crate::{model::{HeaderLabels, Metadata}, util::{db::PostMeta, theme::Theme}};
+use super::{header, post_list};
+
+pub struct SeriesProps {
+    pub series: String,
+    pub posts: Vec<PostMeta>,
+    pub canonical_url: Url,
+    pub coffee_link: Option<Url>,
+    pub site_title: String,
+    pub site_title_short: String,
+    pub theme: Arc<Theme>,
+    pub header_labels: HeaderLabels,
+    pub styles_href: String,
+    pub theme_color: Option<String>,
+    pub color_scheme: Option<String>,
+    pub opensearch: bool,
+    pub metadata: Metadata,
+    pub fragment: bool,
+}
+
+pub fn series(cx: Scope<SeriesProps>) -> Element {
+    let content = rsx! {
+        header::site_header {
+            site_title: &cx.props.site_title,
+            site_title_short: &cx.props.site_title_short,
+            coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str()),
+            theme: &cx.props.theme,
+            labels: &cx.props.header_labels,
+        }
+
+        section {
+            id: "content",
+            h2 { "{cx.props.series}" }
+            post_list::post_list {
+                posts: &cx.props.posts
+            }
+        }
+    };
+
+    if cx.props.fragment {
+        return cx.render(content);
+    }
+
+    cx.render(rsx! {
+        super::preamble {
+            title: &cx.props.series,
+            site_title: &cx.props.site_title,
+            highlight: false,
+            author: cx.props.metadata.author.as_deref(),
+            summary: None,
+            url: &cx.props.canonical_url,
+            styles_href: &cx.props.styles_href,
+            favicon: cx.props.theme.favicon.as_deref(),
+            theme_color: cx.props.theme_color.as_deref(),
+            color_scheme: cx.props.color_scheme.as_deref(),
+            opensearch: cx.props.opensearch,
+        }
+
+        body {
+            main {
+                class: "series",
+                content
+            }
+        }
+    })
+}
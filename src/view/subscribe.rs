@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use dioxus::prelude::*;
+use url::Url;
+
+use crate::{model::{HeaderLabels, Metadata}, util::theme::Theme};
+use super::header;
+
+/// A feed that readers can subscribe to
+pub struct Feed {
+    pub title: String,
+    /// What the feed has in it
+    pub description: String,
+    pub url: Url,
+}
+
+pub struct SubscribeProps {
+    pub feeds: Vec<Feed>,
+    pub canonical_url: Url,
+    pub coffee_link: Option<Url>,
+    pub site_title: String,
+    pub site_title_short: String,
+    pub theme: Arc<Theme>,
+    pub header_labels: HeaderLabels,
+    pub styles_href: String,
+    pub theme_color: Option<String>,
+    pub color_scheme: Option<String>,
+    pub opensearch: bool,
+    pub metadata: Metadata,
+    pub fragment: bool,
+}
+
+pub fn subscribe(cx: Scope<SubscribeProps>) -> Element {
+    let content = rsx! {
+        header::site_header {
+            site_title: &cx.props.site_title,
+            site_title_short: &cx.props.site_title_short,
+            coffee_link: cx.props.coffee_link.as_ref().map(|c| c.as_str()),
+            theme: &cx.props.theme,
+            labels: &cx.props.header_labels,
+        }
+
+        section {
+            id: "content",
+            h2 { "Subscribe" }
+            p {
+                "A feed reader shows you the new posts of every site you follow, in one place. "
+                "Copy one of these addresses into yours to follow {cx.props.site_title}."
+            }
+            ul {
+                class: "feeds",
+                for feed in cx.props.feeds.iter() {
+                    li {
+                        h3 {
+                            a { href: "{feed.url}", "{feed.title}" }
+                        }
+                        p { "{feed.description}" }
+                        input {
+                            r#type: "url",
+                            readonly: true,
+                            aria_label: "Address of {feed.title}",
+                            value: "{feed.url}"
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    if cx.props.fragment {
+        return cx.render(content);
+    }
+
+    cx.render(rsx! {
+        super::preamble {
+            title: "Subscribe",
+            site_title: &cx.props.site_title,
+            highlight: false,
+            author: cx.props.metadata.author.as_deref(),
+            summary: None,
+            url: &cx.props.canonical_url,
+            styles_href: &cx.props.styles_href,
+            favicon: cx.props.theme.favicon.as_deref(),
+            theme_color: cx.props.theme_color.as_deref(),
+            color_scheme: cx.props.color_scheme.as_deref(),
+            opensearch: cx.props.opensearch,
+        }
+
+        body {
+            main {
+                class: "subscribe",
+                content
+            }
+        }
+    })
+}